@@ -1,5 +1,6 @@
 MEMORY
 {
- FLASH : ORIGIN = 0x08000000, LENGTH = 64K
+ /* last two 1K pages are used for settings, see src/hardware/flash_storage.rs */
+ FLASH : ORIGIN = 0x08000000, LENGTH = 62K
  RAM : ORIGIN = 0x20000000, LENGTH = 20K
 }
\ No newline at end of file
@@ -1,50 +1,460 @@
-use crate::button::Button;
+use crate::auto_arm::{AutoArm, ScheduleAction};
+use crate::aux_sensors::{AuxEvent, AuxSensors};
+use crate::buzzer::{Buzzer, MELODY_ERROR, MELODY_EXIT_DELAY_ENDING, MELODY_KEY};
+use crate::button::{Button, Gesture, KnockCode};
+use crate::clock::Clock;
+use crate::config::CONFIG;
+use crate::hardware::rtc_clock::RtcClock;
+use crate::crash_record::{self, CrashKind};
 use crate::door_sensor::{DoorSensor, DoorState};
-use crate::indication::{Indication, IndicationState};
-use crate::sim900::Sim900;
+use crate::event_log::{EventKind, EventLog};
+use crate::hardware::flash_storage::{FlashStorage, StorageKey};
+use crate::heartbeat::Heartbeat;
+use crate::hardware::reset_cause::ResetCause;
+use crate::hardware::watchdog::{Phase, Watchdog};
+use crate::ibutton::{IButton, KeyId};
+use crate::indication::{
+    Indication, IndicationState, OVERLAY_SMS_SENT, OVERLAY_WARNING, OVERLAY_WRONG_CODE,
+};
+use crate::keypad::{Keypad, PinEntry, PinResult};
+use crate::pdu::{self, DeliveryStatus, Pdu};
+use crate::power_monitor::{PowerEvent, PowerMonitor};
+use crate::sim900::{GsmFault, Sim900};
+use crate::siren::Siren;
+use crate::sms_template::{self, Message, Values};
+use crate::motion_sensor::MotionSensor;
+use crate::scheduler::Scheduler;
 use crate::timer::{CounterTypeExt, Timer,Seconds};
+use crate::zone::{ArmedZones, Zone};
+use core::fmt::Write;
+use heapless::consts::*;
+use heapless::String;
+
+
+const INTENT_DISARMED: u16 = 0;
+const INTENT_ARMED: u16 = 1;
+
+/// Zone, tamper and supervision inputs, handed over to main logic together
+pub struct Sensors {
+    pub door: DoorSensor,
+    pub tamper: DoorSensor,
+    pub motion: Option<MotionSensor>,
+    pub aux: AuxSensors,
+    pub power_monitor: PowerMonitor,
+}
 
 struct Resources {
     sim900: Sim900,
     indication: Indication,
     power_button: Button,
+    keypad: Option<Keypad>,
+    pin_entry: PinEntry,
+    ibutton: Option<IButton>,
     door_sensor: DoorSensor,
+    tamper_sensor: DoorSensor,
+    motion_sensor: Option<MotionSensor>,
+    aux_sensors: AuxSensors,
+    power_monitor: PowerMonitor,
     check_state: u8,
+    check_timer: Timer,
+    check_tries: u8,
+    check_fault: Option<GsmFault>,
+    signal: Option<u8>,
+    balance: Option<i32>,
+    balance_low: bool,
+    balance_reported: bool,
+    arm_key: Option<KeyId>,
 }
 
 impl Resources {
+    const SMS_TRY_COUNT: u8 = 3;
+    const REGISTRATION_RETRY: Seconds = Seconds(1);
+    const REGISTRATION_TRIES: u8 = 30;
+
+    ///send sms and show result with leds
+    fn send_sms(&mut self, text: &str) -> bool {
+        let is_sent = self.send_sms_silent(text);
+        self.show_sent(is_sent);
+        is_sent
+    }
+
+    fn show_sent(&mut self, is_sent: bool) {
+        if is_sent {
+            self.indication.show(&OVERLAY_SMS_SENT);
+        } else {
+            let fault = GsmFault::from_state(self.sim900.get_state(), GsmFault::SmsRejected);
+            self.indication.show_error_code(fault.code());
+        }
+    }
+
+    ///placeholder values known to resources
+    fn values(&self) -> Values<'static> {
+        Values {
+            signal: self.signal,
+            balance: self.balance,
+            fault: self.check_fault.map(|x| x.code()),
+            battery_mv: Some(self.power_monitor.battery_mv()),
+            supply_mv: Some(self.power_monitor.supply_mv()),
+            event: EventLog::last(),
+            ..Values::default()
+        }
+    }
+
+    ///render template with values and send it, blocking
+    fn send_message(&mut self, message: Message, values: &Values) -> bool {
+        let text = sms_template::render(message, values);
+        self.send_sms(&text)
+    }
+
+    ///render alarm template and send it with delivery confirmation, blocking
+    fn send_alarm(&mut self, message: Message, values: &Values) -> bool {
+        let text = sms_template::render(message, values);
+        let is_sent = self.send_alarm_silent(&text);
+        self.show_sent(is_sent);
+        is_sent
+    }
+
+    ///power on modem, send sms to configured phone and power off. Blocking
+    fn send_sms_silent(&mut self, text: &str) -> bool {
+        let is_sent = match pdu::encode_sms(CONFIG.phone, text, false) {
+            Ok(pdu) => self.send_pdu(&pdu),
+            Err(_) => false,
+        };
+        self.power_off();
+        is_sent
+    }
+
+    ///like send_sms_silent, but waits for delivery report. Sms which did not reach
+    ///phone is resent, then phone is called. Blocking for minutes
+    fn send_alarm_silent(&mut self, text: &str) -> bool {
+        if !CONFIG.delivery_report {
+            return self.send_sms_silent(text);
+        }
+        let pdu = match pdu::encode_sms(CONFIG.phone, text, true) {
+            Ok(x) => x,
+            Err(_) => return false,
+        };
+        let mut is_sent = false;
+        for _ in 0..=CONFIG.delivery_retries {
+            if !self.send_pdu(&pdu) {
+                continue;
+            }
+            is_sent = true;
+            if self.wait_delivery() {
+                self.power_off();
+                return true;
+            }
+        }
+        EventLog::push(EventKind::NotDelivered);
+        //ringing phone is noticed even when sms is not
+        Watchdog::begin(Phase::SmsSend);
+        let _ = self.sim900.call(CONFIG.phone, CONFIG.call_duration);
+        Watchdog::end();
+        self.power_off();
+        is_sent
+    }
+
+    ///power off modem, blocking
+    fn power_off(&mut self) {
+        Watchdog::begin(Phase::PowerOff);
+        synchronize(|| self.sim900.power_off());
+        Watchdog::end();
+        //check interrupted by sms lost its modem, it starts over
+        self.check_state = 0;
+    }
+
+    ///power on modem if needed and send sms, modem is left powered
+    fn send_pdu(&mut self, pdu: &Pdu) -> bool {
+        let mut is_sent = false;
+        Watchdog::begin(Phase::SmsSend);
+        for _ in 0..Self::SMS_TRY_COUNT {
+            if synchronize(|| self.sim900.power_on()).is_err() {
+                continue;
+            }
+            if self.sim900.setup().is_err() {
+                continue;
+            }
+            Timer::new().wait(10.sec()); //wait for registration in gsm network
+            if self.sim900.send_pdu_sms(pdu) {
+                is_sent = true;
+                break;
+            }
+        }
+        Watchdog::end();
+        is_sent
+    }
+
+    ///wait for status report of last sent sms, false if it failed or did not come in time
+    fn wait_delivery(&mut self) -> bool {
+        let reference = match self.sim900.message_reference() {
+            Some(x) => x,
+            None => return false,
+        };
+        let mut is_delivered = false;
+        let mut timer = Timer::new();
+        Watchdog::begin(Phase::Delivery);
+        while timer.waiting(&CONFIG.delivery_timeout) {
+            match self.sim900.poll_status_report() {
+                Some(report) if report.reference == reference => match report.status {
+                    DeliveryStatus::Pending => {}
+                    DeliveryStatus::Delivered => {
+                        is_delivered = true;
+                        break;
+                    }
+                    DeliveryStatus::Failed => break,
+                },
+                _ => {}
+            }
+        }
+        Watchdog::end();
+        is_delivered
+    }
+
+    fn sound_alarm(&mut self) {
+        Siren::start(CONFIG.siren_pattern, CONFIG.siren_duration);
+    }
+
+    fn chirp(&mut self, count: u8) {
+        if CONFIG.arm_chirp {
+            Siren::chirp(count);
+        }
+    }
+
+    ///first press after alarm only silences siren
+    fn silence_siren(&mut self) -> bool {
+        if Siren::is_active() {
+            Siren::stop();
+            return true;
+        }
+        false
+    }
+
+    ///alarm with siren and sms, blocking
+    fn raise_alarm(&mut self, zone: Zone) {
+        EventLog::push(EventKind::Alarm(zone));
+        self.sound_alarm();
+        let values = Values {
+            zone: Some(zone),
+            ..self.values()
+        };
+        self.send_alarm(Message::Alarm, &values);
+    }
+
+    ///cut or shorted loop while armed is sabotage, alarm like open door. Blocking
+    fn raise_zone_fault_alarm(&mut self) {
+        EventLog::push(EventKind::ZoneFault);
+        self.sound_alarm();
+        self.send_alarm(Message::ZoneFaultAlarm, &self.values());
+    }
+
+    fn motion_poll(&mut self) -> bool {
+        match self.motion_sensor.as_mut() {
+            Some(x) => x.poll(),
+            None => false,
+        }
+    }
+
+    ///code entered on keypad, None without keypad
+    fn pin_poll(&mut self) -> Option<PinResult> {
+        let key = self.keypad.as_mut()?.poll();
+        if key.is_some() && CONFIG.buzzer && !Buzzer::is_playing() {
+            Buzzer::play(&MELODY_KEY, false);
+        }
+        let res = self.pin_entry.poll(key);
+        if let Some(PinResult::Wrong) | Some(PinResult::Locked) = res {
+            self.indication.show(&OVERLAY_WRONG_CODE);
+        }
+        res
+    }
+
+    ///enrolled key touched to reader, unknown ones are logged and reported while armed
+    fn key_poll(&mut self, is_armed: bool) -> Option<KeyId> {
+        let key = self.ibutton.as_mut()?.poll()?;
+        if key.is_enrolled() {
+            EventLog::push(EventKind::Key(key));
+            return Some(key);
+        }
+        EventLog::push(EventKind::UnknownKey(key));
+        self.indication.show(&OVERLAY_WRONG_CODE);
+        if is_armed {
+            self.send_key_sms(Message::UnknownKey, key);
+        }
+        None
+    }
+
+    fn send_key_sms(&mut self, message: Message, key: KeyId) {
+        let values = Values {
+            key: Some(key),
+            ..self.values()
+        };
+        self.send_message(message, &values);
+    }
+
+    fn send_test_sms(&mut self) {
+        self.send_message(Message::Test, &self.values());
+    }
+
+    ///state, signal, balance, supply, uptime and last event, short enough for one sms
+    fn send_status_sms(&mut self, state: IndicationState) {
+        let values = Values {
+            state: Some(state),
+            ..self.values()
+        };
+        self.send_message(Message::Heartbeat, &values);
+    }
+
+    ///check modem and blink signal as 1..5 green flashes or blink error code, blocking
+    fn show_signal(&mut self) {
+        if !self.check_gsm_now() {
+            if let Some(fault) = self.check_fault {
+                self.indication.show_error_code(fault.code());
+            }
+            return;
+        }
+        if let Some(rssi) = self.signal {
+            self.indication.show_level(rssi.div_ceil(6));
+        }
+    }
+
+    fn report_zone_fault(&mut self) {
+        EventLog::push(EventKind::ZoneFault);
+        self.send_message(Message::ZoneFault, &self.values());
+    }
+
+    ///blocking version of check_gsm
+    fn check_gsm_now(&mut self) -> bool {
+        Watchdog::begin(Phase::GsmCheck);
+        let res = synchronize(|| self.check_gsm());
+        Watchdog::end();
+        res
+    }
+
+    ///one step of check_gsm, its requests block for seconds and have to be supervised too
+    fn check_gsm_step(&mut self) -> Option<bool> {
+        Watchdog::begin(Phase::GsmCheck);
+        let res = self.check_gsm();
+        Watchdog::end();
+        res
+    }
+
+    fn power_poll(&mut self) {
+        //modem is busy with check, events will be caught next time
+        if self.check_state != 0 {
+            return;
+        }
+        if let Some(event) = self.power_monitor.poll() {
+            let (message, kind) = match event {
+                PowerEvent::MainsLost => (Message::MainsLost, EventKind::MainsLost),
+                PowerEvent::MainsRestored => (Message::MainsRestored, EventKind::MainsRestored),
+                PowerEvent::BatteryLow => (Message::BatteryLow, EventKind::BatteryLow),
+            };
+            EventLog::push(kind);
+            self.send_message(message, &self.values());
+        }
+        if self.sim900.take_under_voltage() {
+            EventLog::push(EventKind::BatteryLow);
+            self.send_message(Message::BatteryLow, &self.values());
+        }
+    }
+
+    ///temperature, flood and smoke are guarded even when disarmed
+    fn aux_poll(&mut self) {
+        //modem is busy with check, events will be caught next time
+        if self.check_state != 0 {
+            return;
+        }
+        let event = match self.aux_sensors.poll() {
+            Some(x) => x,
+            None => return,
+        };
+        EventLog::push(EventKind::Aux(event));
+        let (message, temperature) = match event {
+            AuxEvent::TemperatureHigh(t) => (Message::TemperatureHigh, Some(t)),
+            AuxEvent::TemperatureLow(t) => (Message::TemperatureLow, Some(t)),
+            AuxEvent::TemperatureNormal(t) => (Message::TemperatureNormal, Some(t)),
+            AuxEvent::TemperatureLost => (Message::TemperatureLost, None),
+            AuxEvent::Flood => (Message::Flood, None),
+            AuxEvent::FloodCleared => (Message::FloodCleared, None),
+            AuxEvent::Smoke => (Message::Smoke, None),
+            AuxEvent::SmokeCleared => (Message::SmokeCleared, None),
+        };
+        if event == AuxEvent::Smoke {
+            self.sound_alarm();
+        }
+        let values = Values {
+            temperature,
+            ..self.values()
+        };
+        match event {
+            AuxEvent::Flood | AuxEvent::Smoke => self.send_alarm(message, &values),
+            _ => self.send_message(message, &values),
+        };
+    }
+
+    fn sync_clock(&mut self) {
+        //network time may be not received yet, try next time
+        if let Ok(time) = self.sim900.get_time() {
+            let before = RtcClock::counter();
+            Clock::set(time);
+            Heartbeat::clock_set(before, RtcClock::counter());
+        }
+    }
+    ///power on modem, check sim, registration, signal and balance, power off
     fn check_gsm(&mut self) -> Option<bool> {
         match self.check_state {
             0 => {
                 if let Some(res) = self.sim900.power_on() {
                     if res.is_err() {
-                        self.check_state = 0;
-                        Some(false)
-                    } else {
-                        //good go next state
-                        self.check_state = 1;
-                        None
+                        return Some(self.check_done(Some(GsmFault::NoModem)));
                     }
-                } else {
-                    None
+                    self.check_fault = None;
+                    //good go next state
+                    self.check_state = 1;
                 }
+                None
             }
             1 => {
                 if self.sim900.setup().is_err() {
-                    self.check_state = 0;
-                    Some(false)
+                    self.check_fault =
+                        Some(GsmFault::from_state(self.sim900.get_state(), GsmFault::NoModem));
+                    self.check_state = 4;
                 } else {
+                    self.check_timer.reset();
+                    self.check_tries = 0;
                     self.check_state = 2;
-                    None //go next
                 }
+                None //go next
             }
             2 => {
+                //registration takes some seconds after power on, ask again later without blocking
+                if self.check_timer.waiting(&Self::REGISTRATION_RETRY) {
+                    return None;
+                }
+                self.check_timer.reset();
+                self.check_tries += 1;
+                if self.sim900.is_registered().is_ok() {
+                    self.check_state = 3;
+                } else if self.check_tries >= Self::REGISTRATION_TRIES {
+                    self.check_fault = Some(GsmFault::from_state(
+                        self.sim900.get_state(),
+                        GsmFault::NoRegistration,
+                    ));
+                    self.check_state = 4;
+                }
+                None
+            }
+            3 => {
+                self.sync_clock();
+                self.check_fault = self.check_signal_and_balance();
+                self.check_state = 4;
+                None
+            }
+            4 => {
                 if self.sim900.power_off().is_some() {
-                    self.check_state = 0;
-                    Some(true)
-                } else {
-                    None
+                    let fault = self.check_fault;
+                    return Some(self.check_done(fault));
                 }
+                None
             }
             _ => {
                 self.check_state = 0;
@@ -52,10 +462,61 @@ impl Resources {
             }
         }
     }
+
+    fn check_signal_and_balance(&mut self) -> Option<GsmFault> {
+        self.signal = self.sim900.signal_quality().ok().filter(|x| *x != 99);
+        match self.signal {
+            Some(rssi) if rssi >= CONFIG.signal_min => {}
+            _ => return Some(GsmFault::WeakSignal),
+        }
+        if let Some(ussd) = CONFIG.balance_ussd {
+            //operator may not answer, only known low balance is a warning
+            self.balance = self.sim900.get_balance(ussd).ok();
+            self.balance_low = self.balance.is_some_and(|x| x < CONFIG.balance_min);
+        }
+        None
+    }
+
+    ///finish check, blink code of fault is shown in error state
+    fn check_done(&mut self, fault: Option<GsmFault>) -> bool {
+        self.check_state = 0;
+        self.check_fault = fault;
+        self.indication.set_error_code(fault.map_or(0, |x| x.code()));
+        if fault.is_none() && self.balance_low {
+            //warning only, blinked once over current indication
+            self.indication.show_error_code(GsmFault::LowBalance.code());
+        }
+        fault.is_none()
+    }
+
+    ///low balance is reported once until account is topped up
+    fn balance_poll(&mut self) {
+        //modem is busy with check, balance will be reported next time
+        if self.check_state != 0 || self.balance_low == self.balance_reported {
+            return;
+        }
+        self.balance_reported = self.balance_low;
+        if self.balance_low {
+            self.send_message(Message::BalanceLow, &self.values());
+        }
+    }
 }
+/// periodic background work, registered in scheduler
+#[derive(Copy, Clone, PartialEq)]
+enum Job {
+    GsmCheck,
+    AutoArm,
+    Heartbeat,
+}
+
 pub struct MainLogic {
     resources: Resources,
     current_state: AlgorithmState,
+    scheduler: Scheduler<Job>,
+    auto_arm: AutoArm,
+    was_armed: bool,
+    //job whose gsm check runs step by step from poll
+    pending_check: Option<Job>,
 }
 #[allow(dead_code)]
 fn synchronize<T: Sized>(mut f: impl FnMut() -> Option<T>) -> T {
@@ -63,6 +524,7 @@ fn synchronize<T: Sized>(mut f: impl FnMut() -> Option<T>) -> T {
         if let Some(x) = f() {
             return x;
         }
+        Watchdog::feed_in_phase();
     }
 }
 
@@ -71,8 +533,11 @@ enum AlgorithmState {
     IdleDoorClosedState(IdleDoorClosed),
     CheckState(Check),
     ReadyToArmState(ReadyToArm),
+    ExitDelayState(ExitDelay),
     ArmedState(Armed),
+    EntryDelayState(EntryDelay),
     ErrorState(Error),
+    TamperState(Tamper),
 }
 #[allow(dead_code)]
 impl MainLogic {
@@ -80,50 +545,378 @@ impl MainLogic {
         sim900: Sim900,
         indication: Indication,
         power_button: Button,
-        door_sensor: DoorSensor,
+        keypad: Option<Keypad>,
+        ibutton: Option<IButton>,
+        sensors: Sensors,
     ) -> MainLogic {
         MainLogic {
             resources: Resources {
                 sim900,
                 indication,
                 power_button,
-                door_sensor,
+                keypad,
+                pin_entry: PinEntry::new(),
+                ibutton,
+                door_sensor: sensors.door,
+                tamper_sensor: sensors.tamper,
+                motion_sensor: sensors.motion,
+                aux_sensors: sensors.aux,
+                power_monitor: sensors.power_monitor,
                 check_state: 0,
+                check_timer: Timer::new(),
+                check_tries: 0,
+                check_fault: None,
+                signal: None,
+                balance: None,
+                balance_low: false,
+                balance_reported: false,
+                arm_key: None,
             },
             current_state: AlgorithmState::IdleState(Idle {}),
+            scheduler: Scheduler::new(RtcClock::counter()),
+            auto_arm: AutoArm::new(),
+            was_armed: false,
+            pending_check: None,
         }
     }
 
-    fn update_view(&mut self) {
-        let new_view_state = match self.current_state {
+    fn view_state(&self) -> IndicationState {
+        match self.current_state {
             AlgorithmState::IdleState(_) => IndicationState::Idle,
             AlgorithmState::IdleDoorClosedState(_) => IndicationState::IdleDoorClosed,
             AlgorithmState::CheckState(_) => IndicationState::CheckBeforeArm,
             AlgorithmState::ReadyToArmState(_) => IndicationState::ReadyToArm,
+            AlgorithmState::ExitDelayState(_) => IndicationState::ExitDelay,
             AlgorithmState::ArmedState(_) => IndicationState::Armed,
+            AlgorithmState::EntryDelayState(_) => IndicationState::EntryDelay,
             AlgorithmState::ErrorState(_) => IndicationState::Error,
-        };
+            AlgorithmState::TamperState(_) => IndicationState::Tamper,
+        }
+    }
+
+    fn update_view(&mut self) {
+        let new_view_state = self.view_state();
         self.resources.indication.set_state(new_view_state);
     }
 
-    pub fn init(&mut self) {
+    ///remember if user wants device armed, so reset or power loss does not disarm it
+    fn save_intent(&mut self) {
+        let intent = match self.current_state {
+            //user may be still inside while arming, reset then must not come back armed
+            AlgorithmState::IdleState(_)
+            | AlgorithmState::IdleDoorClosedState(_)
+            | AlgorithmState::CheckState(_)
+            | AlgorithmState::ReadyToArmState(_)
+            | AlgorithmState::ExitDelayState(_) => INTENT_DISARMED,
+            AlgorithmState::ArmedState(_) | AlgorithmState::EntryDelayState(_) => INTENT_ARMED,
+            //e.g. scheduled arming with failed check, zones are guarded already
+            AlgorithmState::ErrorState(ref x) if x.armed.is_some() => INTENT_ARMED,
+            //error and tamper keep state they came from
+            AlgorithmState::ErrorState(_) | AlgorithmState::TamperState(_) => return,
+        };
+        FlashStorage::write(StorageKey::ArmedIntent, intent);
+    }
+
+    fn state_changed(&mut self) {
+        self.update_view();
+        self.save_intent();
+        //short openings are caught by edge only while armed
+        let is_armed = matches!(self.current_state, AlgorithmState::ArmedState(_));
+        self.resources.door_sensor.set_latching(is_armed);
+        if let Some(x) = self.resources.motion_sensor.as_mut() {
+            x.set_latching(is_armed);
+        }
+        self.arm_sms_poll();
+    }
+
+    ///sms when armed or disarmed, error and tamper do not change it
+    fn arm_sms_poll(&mut self) {
+        let is_armed = match self.current_state {
+            AlgorithmState::ArmedState(_) | AlgorithmState::EntryDelayState(_) => true,
+            AlgorithmState::IdleState(_) | AlgorithmState::IdleDoorClosedState(_) => false,
+            _ => return,
+        };
+        //key is reported only once device is armed, aborted arming forgets it
+        let key = self.resources.arm_key.take();
+        if is_armed == self.was_armed {
+            return;
+        }
+        self.was_armed = is_armed;
+        if let (true, Some(key)) = (is_armed, key) {
+            self.resources.send_key_sms(Message::KeyArmed, key);
+        } else if CONFIG.arm_sms {
+            let message = if is_armed { Message::Arm } else { Message::Disarm };
+            self.resources
+                .send_message(message, &self.resources.values());
+        }
+    }
+
+    fn register_jobs(&mut self) {
+        let _ = self
+            .scheduler
+            .add(Job::GsmCheck, "gsm check", 24.hours(), 10.minutes());
+        if !CONFIG.schedule.is_empty() {
+            let _ = self
+                .scheduler
+                .add(Job::AutoArm, "auto arm", 20.sec(), 0.sec());
+        }
+        if CONFIG.heartbeat.is_some() {
+            Heartbeat::init();
+            let _ = self
+                .scheduler
+                .add(Job::Heartbeat, "heartbeat", 1.minutes(), 0.sec());
+        }
+    }
+
+    fn run_job(&mut self, job: Job) {
+        match job {
+            Job::GsmCheck => {
+                //modem is already checked in these states
+                if let AlgorithmState::CheckState(_) | AlgorithmState::ErrorState(_) =
+                    self.current_state
+                {
+                    return;
+                }
+                //blocking check would stop polling of zones for a minute
+                if self.pending_check.is_none() {
+                    self.pending_check = Some(Job::GsmCheck);
+                }
+            }
+            Job::Heartbeat => {
+                //modem is busy with check, try next minute
+                if self.pending_check.is_some() || !Heartbeat::is_due() {
+                    return;
+                }
+                //sms is sent when check is done, until then heartbeat stays due
+                self.pending_check = Some(Job::Heartbeat);
+            }
+            Job::AutoArm => {
+                if let Some(action) = self.auto_arm.poll() {
+                    if let Some(x) = self.scheduled_action(action) {
+                        self.current_state = x;
+                        self.state_changed();
+                    }
+                }
+            }
+        }
+    }
+
+    ///one step of background check, result is handled by job which started it
+    fn check_poll(&mut self) {
+        let job = match self.pending_check {
+            Some(x) => x,
+            None => return,
+        };
+        if let AlgorithmState::CheckState(_) | AlgorithmState::ErrorState(_) = self.current_state {
+            //state took over modem, its own check goes on
+            self.pending_check = None;
+            return;
+        }
+        let is_ok = match self.resources.check_gsm_step() {
+            Some(x) => x,
+            None => return,
+        };
+        self.pending_check = None;
+        match (job, is_ok) {
+            (Job::Heartbeat, true) => {
+                Heartbeat::schedule_next();
+                self.resources.send_status_sms(self.view_state());
+            }
+            (Job::Heartbeat, false) => {
+                Heartbeat::schedule_next();
+                self.gsm_fault(Message::Heartbeat);
+            }
+            (_, false) => self.gsm_fault(Message::GsmFault),
+            _ => {}
+        }
+    }
+
+    ///fault found by background check. Guarding states keep watching zones and only blink
+    ///fault code, others wait for modem in error state. Sms may still get through
+    fn gsm_fault(&mut self, message: Message) {
+        let values = Values {
+            state: Some(self.view_state()),
+            ..self.resources.values()
+        };
+        self.resources.send_message(message, &values);
+        match self.current_state {
+            AlgorithmState::ExitDelayState(_)
+            | AlgorithmState::ArmedState(_)
+            | AlgorithmState::EntryDelayState(_)
+            | AlgorithmState::TamperState(_) => {
+                if let Some(fault) = self.resources.check_fault {
+                    self.resources.indication.show_error_code(fault.code());
+                }
+            }
+            AlgorithmState::ErrorState(_) => {}
+            _ => {
+                self.current_state = AlgorithmState::ErrorState(Error::new(self.view_state()));
+                self.state_changed();
+            }
+        }
+    }
+
+    ///arm only from disarmed states and disarm only armed ones
+    fn scheduled_action(&mut self, action: ScheduleAction) -> Option<AlgorithmState> {
+        let resources = &mut self.resources;
+        match (action, &self.current_state) {
+            (ScheduleAction::Arm, AlgorithmState::IdleState(_))
+            | (ScheduleAction::Arm, AlgorithmState::IdleDoorClosedState(_)) => {
+                if !resources.door_sensor.is_closed() {
+                    EventLog::push(EventKind::AutoArmFailed);
+                    resources.indication.show(&OVERLAY_WARNING);
+                    if CONFIG.buzzer {
+                        Buzzer::play(&MELODY_ERROR, false);
+                    }
+                    resources.send_message(Message::AutoArmFailed, &resources.values());
+                    return None;
+                }
+                //nobody is inside, no exit delay
+                if !resources.check_gsm_now() {
+                    return Some(AlgorithmState::ErrorState(Error::new(IndicationState::Armed)));
+                }
+                EventLog::push(EventKind::AutoArm);
+                resources.chirp(1);
+                Some(AlgorithmState::ArmedState(Armed::new()))
+            }
+            //error of disarmed device only waits for modem
+            (ScheduleAction::Disarm, AlgorithmState::ErrorState(x)) if x.armed.is_none() => None,
+            (ScheduleAction::Disarm, AlgorithmState::CheckState(_))
+            | (ScheduleAction::Disarm, AlgorithmState::ReadyToArmState(_))
+            | (ScheduleAction::Disarm, AlgorithmState::ExitDelayState(_))
+            | (ScheduleAction::Disarm, AlgorithmState::ArmedState(_))
+            | (ScheduleAction::Disarm, AlgorithmState::EntryDelayState(_))
+            | (ScheduleAction::Disarm, AlgorithmState::ErrorState(_)) => {
+                let is_checking = match &self.current_state {
+                    AlgorithmState::CheckState(_) => true,
+                    AlgorithmState::ErrorState(x) => x.flag_go_check,
+                    _ => false,
+                };
+                if is_checking {
+                    //let started check finish, so modem is powered off and background polls resume
+                    resources.check_gsm_now();
+                }
+                EventLog::push(EventKind::AutoDisarm);
+                resources.chirp(2);
+                Some(AlgorithmState::IdleState(Idle {}))
+            }
+            _ => None,
+        }
+    }
+
+    ///nothing to poll often: steady indication, sensors idle and no job is close
+    pub fn can_stop(&self) -> bool {
+        let quiet_state = matches!(
+            self.current_state,
+            AlgorithmState::IdleState(_) | AlgorithmState::ArmedState(_)
+        );
+        let job_is_close = match self.scheduler.next_deadline() {
+            Some(deadline) => !Timer::now().checked_add(1.sec()).is_some_and(|t| t < deadline),
+            None => false,
+        };
+        quiet_state
+            && !job_is_close
+            && self.resources.power_button.is_idle()
+            && self.resources.keypad.as_ref().is_none_or(|x| x.is_idle())
+            && self.resources.door_sensor.is_settled()
+            && self.resources.tamper_sensor.is_settled()
+            && self.resources.motion_sensor.as_ref().is_none_or(|x| x.is_settled())
+            && self.resources.aux_sensors.is_settled()
+            && !Siren::is_active()
+            && !Buzzer::is_playing()
+            && !self.resources.indication.has_overlay()
+            && self.pending_check.is_none()
+    }
+
+    ///tamper is guarded in every state, even when disarmed
+    fn tamper_poll(&mut self) {
+        if let AlgorithmState::TamperState(_) = self.current_state {
+            return;
+        }
+        if let Some(DoorState::Opened) = self.resources.tamper_sensor.state() {
+            EventLog::push(EventKind::Tamper);
+            //error waits for modem, go back to state it came from
+            let from_state = match &self.current_state {
+                AlgorithmState::ErrorState(x) => x.from_state,
+                _ => self.view_state(),
+            };
+            self.current_state = AlgorithmState::TamperState(Tamper {
+                from_state,
+                zones: ArmedZones::new(),
+            });
+            self.state_changed();
+            self.resources.sound_alarm();
+            self.resources.send_alarm(Message::Tamper, &self.resources.values());
+        }
+    }
+
+    fn report_crash(&mut self) {
+        let record = match crash_record::take() {
+            Some(x) => x,
+            None => return,
+        };
+        EventLog::push(EventKind::Crash(record.info()));
+        if !CONFIG.crash_sms {
+            return;
+        }
+        let mut detail: String<U128> = String::new();
+        let _ = match record.kind() {
+            CrashKind::Panic => write!(detail, "{}", record.message()),
+            CrashKind::HardFault => write!(detail, "HardFault PC={:08X}", record.pc()),
+        };
+        let values = Values {
+            detail: Some(&detail),
+            ..self.resources.values()
+        };
+        self.resources.send_message(Message::CrashReset, &values);
+    }
+
+    pub fn init(&mut self, reset_cause: ResetCause) {
+        self.register_jobs();
+        //restored state is not a new arm
+        self.was_armed = FlashStorage::read(StorageKey::ArmedIntent) == Some(INTENT_ARMED);
+        EventLog::push(EventKind::Reset(reset_cause));
+        self.report_crash();
+        if let ResetCause::IndependentWatchdog = reset_cause {
+            self.resources
+                .send_message(Message::WatchdogReset, &self.resources.values());
+        }
         if let AlgorithmState::IdleState(x) = self.current_state {
             self.current_state = x.init(&mut self.resources);
-            self.update_view();
+            self.state_changed();
         }
     }
     pub fn poll(&mut self) {
         self.resources.indication.poll();
+        self.tamper_poll();
         if let Some(x) = self.current_state.poll(&mut self.resources) {
             self.current_state = x;
-            self.update_view();
+            self.state_changed();
+        }
+        self.check_poll();
+        self.resources.power_poll();
+        self.resources.aux_poll();
+        self.resources.balance_poll();
+        if let Some(job) = self.scheduler.poll() {
+            self.run_job(job);
         }
+        Watchdog::feed();
     }
 }
 
 struct Check {}
 struct Armed {
-    try_count: u8,
+    zones: ArmedZones,
+}
+struct ExitDelay {
+    timer: Timer,
+    is_ending: bool,
+}
+struct EntryDelay {
+    timer: Timer,
+    knock: KnockCode,
+    wrong_codes: u8,
+    zone: Zone,
 }
 struct ReadyToArm {}
 struct Error {
@@ -131,33 +924,211 @@ struct Error {
     timeout: Seconds,
     from_state: IndicationState,
     flag_go_check: bool,
+    //device which should be armed keeps guarding while modem is checked
+    armed: Option<Armed>,
 }
 #[derive(Copy, Clone)]
 struct Idle {}
 struct IdleDoorClosed {}
+struct Tamper {
+    from_state: IndicationState,
+    zones: ArmedZones,
+}
+
+///state to go back after error or tamper
+fn restore_state(from_state: IndicationState) -> AlgorithmState {
+    match from_state {
+        IndicationState::CheckBeforeArm => AlgorithmState::CheckState(Check {}),
+        IndicationState::ReadyToArm | IndicationState::ExitDelay => {
+            AlgorithmState::ReadyToArmState(ReadyToArm {})
+        }
+        IndicationState::Armed => AlgorithmState::ArmedState(Armed::new()),
+        IndicationState::EntryDelay => AlgorithmState::EntryDelayState(EntryDelay::new()),
+        _ => AlgorithmState::IdleState(Idle {}),
+    }
+}
+///too many wrong codes while armed, blocking
+fn code_lockout(resources: &mut Resources) -> Option<AlgorithmState> {
+    EventLog::push(EventKind::CodeLockout);
+    resources.sound_alarm();
+    resources.send_alarm(Message::CodeLockout, &resources.values());
+    Some(AlgorithmState::IdleState(Idle {}))
+}
+
+///user code or key disarms, duress code disarms and silently reports
+fn armed_keypad_poll(resources: &mut Resources) -> Option<AlgorithmState> {
+    if let Some(key) = resources.key_poll(true) {
+        //tamper alarm may still sound
+        resources.silence_siren();
+        resources.chirp(2);
+        resources.indication.set_state(IndicationState::Idle);
+        resources.send_key_sms(Message::KeyDisarmed, key);
+        return Some(AlgorithmState::IdleState(Idle {}));
+    }
+    match resources.pin_poll()? {
+        PinResult::User(_) => {
+            resources.silence_siren();
+            resources.chirp(2);
+            Some(AlgorithmState::IdleState(Idle {}))
+        }
+        PinResult::Duress => {
+            EventLog::push(EventKind::Duress);
+            resources.silence_siren();
+            resources.chirp(2);
+            //look disarmed while sms is sent
+            resources.indication.set_state(IndicationState::Idle);
+            let text = sms_template::render(Message::Duress, &resources.values());
+            resources.send_alarm_silent(&text);
+            Some(AlgorithmState::IdleState(Idle {}))
+        }
+        PinResult::Wrong => None,
+        PinResult::Locked => code_lockout(resources),
+    }
+}
+
+///long press disarms, with knock code set button is ignored
+fn armed_button_poll(resources: &mut Resources) -> Option<AlgorithmState> {
+    if let Some(x) = armed_keypad_poll(resources) {
+        return Some(x);
+    }
+    let gesture = resources.power_button.poll();
+    //accidental short press must not disarm
+    if let (true, Some(Gesture::Long)) = (CONFIG.disarm_code.is_empty(), gesture) {
+        resources.silence_siren();
+        resources.chirp(2);
+        return Some(AlgorithmState::IdleState(Idle {}));
+    }
+    None
+}
+
+///knock code is taken only during entry delay, too many wrong codes raise alarm
+fn entry_button_poll(
+    resources: &mut Resources,
+    knock: &mut KnockCode,
+    wrong_codes: &mut u8,
+) -> Option<AlgorithmState> {
+    if CONFIG.disarm_code.is_empty() {
+        return armed_button_poll(resources);
+    }
+    if let Some(x) = armed_keypad_poll(resources) {
+        return Some(x);
+    }
+    let gesture = resources.power_button.poll();
+    match knock.poll(gesture, CONFIG.disarm_code)? {
+        true => {
+            resources.chirp(2);
+            Some(AlgorithmState::IdleState(Idle {}))
+        }
+        false => {
+            *wrong_codes += 1;
+            if *wrong_codes < CONFIG.disarm_attempts {
+                resources.indication.show(&OVERLAY_WRONG_CODE);
+                return None;
+            }
+            //blocking code here
+            code_lockout(resources)
+        }
+    }
+}
+
+///not armed yet, any press, user code or key cancels
+fn arming_button_poll(resources: &mut Resources) -> Option<AlgorithmState> {
+    if resources.key_poll(false).is_some() {
+        return Some(AlgorithmState::IdleState(Idle {}));
+    }
+    match resources.pin_poll() {
+        Some(PinResult::User(_)) | Some(PinResult::Duress) => {
+            return Some(AlgorithmState::IdleState(Idle {}))
+        }
+        _ => {}
+    }
+    match resources.power_button.poll() {
+        Some(Gesture::Short) | Some(Gesture::Long) => Some(AlgorithmState::IdleState(Idle {})),
+        _ => None,
+    }
+}
+
+///short press, user code or key arms, double press sends test sms, long press shows signal
+fn disarmed_button_poll(resources: &mut Resources) -> Option<AlgorithmState> {
+    let key = resources.key_poll(false);
+    let is_code = matches!(
+        resources.pin_poll(),
+        Some(PinResult::User(_)) | Some(PinResult::Duress)
+    );
+    if key.is_some() || is_code {
+        if resources.silence_siren() {
+            return None;
+        }
+        resources.arm_key = key;
+        return Some(AlgorithmState::CheckState(Check {}));
+    }
+    let gesture = resources.power_button.poll()?;
+    if gesture == Gesture::Press || gesture == Gesture::Release {
+        return None;
+    }
+    //first gesture after alarm only silences siren
+    if resources.silence_siren() {
+        return None;
+    }
+    match gesture {
+        Gesture::Short => Some(AlgorithmState::CheckState(Check {})),
+        Gesture::Double => {
+            resources.send_test_sms();
+            None
+        }
+        Gesture::Long => {
+            resources.show_signal();
+            None
+        }
+        _ => None,
+    }
+}
 #[allow(dead_code)]
 impl Idle {
     pub fn init(self, resources: &mut Resources) -> AlgorithmState {
-        if !synchronize(|| resources.check_gsm()) {
-            return AlgorithmState::ErrorState(Error::new(IndicationState::Idle));
+        let was_armed = FlashStorage::read(StorageKey::ArmedIntent) == Some(INTENT_ARMED);
+        if !resources.check_gsm_now() {
+            let from_state = match was_armed {
+                true => IndicationState::Armed,
+                false => IndicationState::Idle,
+            };
+            return AlgorithmState::ErrorState(Error::new(from_state));
         }
-        match resources.door_sensor.is_closed() {
-            true => AlgorithmState::ArmedState(Armed::new()),
-            false => AlgorithmState::IdleState(self),
+        if !was_armed {
+            return match resources.door_sensor.is_closed() {
+                true => AlgorithmState::IdleDoorClosedState(IdleDoorClosed {}),
+                false => AlgorithmState::IdleState(self),
+            };
+        }
+        EventLog::push(EventKind::PowerCycledArmed);
+        resources.send_message(Message::PowerCycledArmed, &resources.values());
+        //blocking code here
+        match resources.door_sensor.settle() {
+            DoorState::Closed => AlgorithmState::ArmedState(Armed::new()),
+            DoorState::Opened => {
+                //door was opened while device was off
+                resources.raise_alarm(Zone::Door);
+                AlgorithmState::IdleState(self)
+            }
+            DoorState::Fault => {
+                //loop was cut or shorted while device was off
+                resources.raise_zone_fault_alarm();
+                AlgorithmState::IdleState(self)
+            }
         }
     }
     fn door_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
-        if let Some(DoorState::Closed) = resources.door_sensor.state() {
-            return Some(AlgorithmState::IdleDoorClosedState(IdleDoorClosed{}));
+        match resources.door_sensor.state() {
+            Some(DoorState::Closed) => Some(AlgorithmState::IdleDoorClosedState(IdleDoorClosed{})),
+            Some(DoorState::Fault) => {
+                resources.report_zone_fault();
+                None
+            }
+            _ => None,
         }
-        None
     }
     fn button_poll(&self, resources: &mut Resources) -> Option<AlgorithmState> {
-        if let Some(true) = resources.power_button.is_pressed() {
-            Some(AlgorithmState::CheckState(Check {}))
-        } else {
-            None
-        }
+        disarmed_button_poll(resources)
     }
     fn poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
         if let Some(x) = self.button_poll(resources) {
@@ -173,18 +1144,18 @@ impl Idle {
 #[allow(dead_code)]
 impl IdleDoorClosed {
     fn door_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
-        if let Some(DoorState::Opened) = resources.door_sensor.state() {
-            return Some(AlgorithmState::IdleState(Idle{}));
+        match resources.door_sensor.state() {
+            Some(DoorState::Opened) => Some(AlgorithmState::IdleState(Idle{})),
+            Some(DoorState::Fault) => {
+                resources.report_zone_fault();
+                Some(AlgorithmState::IdleState(Idle{}))
+            }
+            _ => None,
         }
-        None
     }
 
     fn button_poll(&self, resources: &mut Resources) -> Option<AlgorithmState> {
-        if let Some(true) = resources.power_button.is_pressed() {
-            Some(AlgorithmState::CheckState(Check {}))
-        } else {
-            None
-        }
+        disarmed_button_poll(resources)
     }
     fn poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
         if let Some(x) = self.button_poll(resources) {
@@ -200,7 +1171,7 @@ impl IdleDoorClosed {
 #[allow(dead_code)]
 impl Check {
     fn gsm_poll(&self, resources: &mut Resources) -> Option<AlgorithmState> {
-        if let Some(gsm_good) = resources.check_gsm() {
+        if let Some(gsm_good) = resources.check_gsm_step() {
             return match gsm_good {
                 true => Some(AlgorithmState::ReadyToArmState(ReadyToArm {})),
                 false => Some(AlgorithmState::ErrorState(Error::new(
@@ -220,11 +1191,16 @@ impl Check {
 #[allow(dead_code)]
 impl Error {
     pub fn new(from_state: IndicationState) -> Self {
+        let armed = match from_state {
+            IndicationState::Armed | IndicationState::EntryDelay => Some(Armed::new()),
+            _ => None,
+        };
         Self {
             timer: Timer::new(),
             from_state,
             timeout:10.sec(),
             flag_go_check: false,
+            armed,
         }
     }
     fn gsm_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
@@ -236,42 +1212,46 @@ impl Error {
         if !self.flag_go_check {
             return None;
         }
-        if let Some(gsm_good) = resources.check_gsm() {
+        if let Some(gsm_good) = resources.check_gsm_step() {
+            //next check after timeout, zones are polled meanwhile
+            self.flag_go_check = false;
             if !gsm_good {
                 if self.timeout<60.sec(){
                     self.timeout = self.timeout+10.sec();
                 }
                 return None;
             }
-            return match self.from_state {
-                IndicationState::CheckBeforeArm => Some(AlgorithmState::CheckState(Check {})),
-                IndicationState::ReadyToArm => Some(AlgorithmState::ReadyToArmState(ReadyToArm {})),
-                IndicationState::Armed => Some(AlgorithmState::ArmedState(Armed::new())),
-                _ => Some(AlgorithmState::IdleState(Idle {})),
-            };
+            return Some(restore_state(self.from_state));
         }
         return None;
     }
 
     pub fn poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
+        //check owns modem until it is done, state is not left in the middle of it
+        if !self.flag_go_check {
+            if let Some(x) = self.armed.as_mut().and_then(|x| x.poll(resources)) {
+                return Some(x);
+            }
+        }
         self.gsm_poll(resources)
     }
 }
 #[allow(dead_code)]
 impl ReadyToArm {
     fn button_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
-        if let Some(true) = resources.power_button.is_pressed() {
-            return Some(AlgorithmState::IdleState(Idle {}));
-        }
-        None
+        arming_button_poll(resources)
     }
     fn door_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
-        if let Some(DoorState::Closed) = resources.door_sensor.state() {
-            //armed here
-            //add actions on arm here
-            return Some(AlgorithmState::ArmedState(Armed::new()));
+        match resources.door_sensor.state() {
+            Some(DoorState::Closed) => {
+                Some(ExitDelay::start(resources))
+            }
+            Some(DoorState::Fault) => {
+                resources.report_zone_fault();
+                None
+            }
+            _ => None,
         }
-        None
     }
     fn poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
         if let Some(x) = self.button_poll(resources) {
@@ -286,51 +1266,45 @@ impl ReadyToArm {
 #[allow(dead_code)]
 impl Armed {
     fn new() -> Self {
-        Self { try_count: 3 }
-    }
-    fn try_alarm(&mut self, resources: &mut Resources) {
-
-        while self.try_count > 0 {
-            self.try_count = self.try_count - 1;
-            
-
-            if synchronize(|| resources.sim900.power_on()).is_err() {
-                continue;
-            }
-            if resources.sim900.setup().is_err() {
-                continue;
-            }
-            Timer::new().wait(10.sec()); //wait for registration in gsm network
-            if resources.sim900.send_pdu_sms(
-                "0001000B919741123274F200082E0422044004350432043E043304300021000A0414043204350440044C0020043E0442043A0440044B044204300021",
-            ) 
-            {
-                break;
-            }
+        Self {
+            zones: ArmedZones::new(),
         }
-        synchronize(||resources.sim900.power_off());
     }
 
     fn button_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
-        if let Some(true) = resources.power_button.is_pressed() {
-            return Some(AlgorithmState::IdleState(Idle {}));
-        }
-        None
+        armed_button_poll(resources)
     }
-    fn door_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
-        if let Some(DoorState::Opened) = resources.door_sensor.state() {
-            //actions on alarm
+    fn zone_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
+        let zone = match resources.door_sensor.state() {
+            Some(DoorState::Opened) => Zone::Door,
+            Some(DoorState::Fault) => {
+                //blocking code here
+                resources.raise_zone_fault_alarm();
+                return Some(AlgorithmState::IdleState(Idle {}));
+            }
+            _ if resources.motion_poll() => Zone::Motion,
+            _ => return None,
+        };
+        if !self.zones.trip(zone) {
+            return None;
+        }
+        if CONFIG.entry_delay.0 == 0 {
             //blocking code here
-            self.try_alarm(resources);
+            resources.raise_alarm(zone);
             return Some(AlgorithmState::IdleState(Idle {}));
         }
-        None
+        Some(AlgorithmState::EntryDelayState(EntryDelay {
+            timer: Timer::new(),
+            knock: KnockCode::new(),
+            wrong_codes: 0,
+            zone,
+        }))
     }
     fn poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
         if let Some(x) = self.button_poll(resources) {
             return Some(x);
         }
-        if let Some(x) = self.door_poll(resources) {
+        if let Some(x) = self.zone_poll(resources) {
             return Some(x);
         }
         return None;
@@ -344,8 +1318,151 @@ impl AlgorithmState {
             AlgorithmState::IdleDoorClosedState(x) => x.poll(resources),
             AlgorithmState::CheckState(x) => x.poll(resources),
             AlgorithmState::ReadyToArmState(x) => x.poll(resources),
+            AlgorithmState::ExitDelayState(x) => x.poll(resources),
             AlgorithmState::ArmedState(x) => x.poll(resources),
+            AlgorithmState::EntryDelayState(x) => x.poll(resources),
             AlgorithmState::ErrorState(x) => x.poll(resources),
+            AlgorithmState::TamperState(x) => x.poll(resources),
+        }
+    }
+}
+#[allow(dead_code)]
+impl Tamper {
+    ///zones are still guarded if device was armed, siren is already on so no entry delay
+    fn zone_poll(&mut self, resources: &mut Resources) {
+        match self.from_state {
+            IndicationState::Armed | IndicationState::EntryDelay => {}
+            _ => return,
+        }
+        let zone = match resources.door_sensor.state() {
+            Some(DoorState::Opened) => Zone::Door,
+            Some(DoorState::Fault) => {
+                //blocking code here
+                resources.raise_zone_fault_alarm();
+                self.from_state = IndicationState::Idle;
+                return;
+            }
+            _ if resources.motion_poll() => Zone::Motion,
+            _ => return,
+        };
+        if !self.zones.trip(zone) {
+            return;
+        }
+        //blocking code here
+        resources.raise_alarm(zone);
+        //alarm disarms as in armed state
+        self.from_state = IndicationState::Idle;
+    }
+    ///disarm silences siren and goes to idle even with case still open
+    fn button_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
+        match self.from_state {
+            IndicationState::Armed | IndicationState::EntryDelay => armed_button_poll(resources),
+            _ => {
+                let is_key = resources.key_poll(false).is_some();
+                let is_code = matches!(
+                    resources.pin_poll(),
+                    Some(PinResult::User(_)) | Some(PinResult::Duress)
+                );
+                let is_press = matches!(
+                    resources.power_button.poll(),
+                    Some(Gesture::Short) | Some(Gesture::Long)
+                );
+                if !is_key && !is_code && !is_press {
+                    return None;
+                }
+                resources.silence_siren();
+                Some(AlgorithmState::IdleState(Idle {}))
+            }
+        }
+    }
+    fn poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
+        if let Some(x) = self.button_poll(resources) {
+            return Some(x);
+        }
+        if let Some(DoorState::Closed) = resources.tamper_sensor.state() {
+            return Some(restore_state(self.from_state));
+        }
+        self.zone_poll(resources);
+        None
+    }
+}
+#[allow(dead_code)]
+impl ExitDelay {
+    const ENDING: Seconds = Seconds(5);
+    ///door is closed, arm now or after exit delay
+    fn start(resources: &mut Resources) -> AlgorithmState {
+        if CONFIG.exit_delay.0 == 0 {
+            resources.chirp(1);
+            return AlgorithmState::ArmedState(Armed::new());
+        }
+        AlgorithmState::ExitDelayState(ExitDelay {
+            timer: Timer::new(),
+            is_ending: false,
+        })
+    }
+    fn button_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
+        arming_button_poll(resources)
+    }
+    fn door_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
+        match resources.door_sensor.state() {
+            Some(DoorState::Opened) => Some(AlgorithmState::ReadyToArmState(ReadyToArm {})),
+            Some(DoorState::Fault) => {
+                resources.report_zone_fault();
+                Some(AlgorithmState::ReadyToArmState(ReadyToArm {}))
+            }
+            _ => None,
+        }
+    }
+    fn timer_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
+        if !self.is_ending && !self.timer.waiting(&(CONFIG.exit_delay - Self::ENDING)) {
+            //hurry up beeps for last seconds
+            self.is_ending = true;
+            if CONFIG.buzzer {
+                Buzzer::play(&MELODY_EXIT_DELAY_ENDING, true);
+            }
+        }
+        if !self.timer.waiting(&CONFIG.exit_delay) {
+            //armed here
+            resources.chirp(1);
+            return Some(AlgorithmState::ArmedState(Armed::new()));
+        }
+        None
+    }
+    fn poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
+        if let Some(x) = self.button_poll(resources) {
+            return Some(x);
+        }
+        if let Some(x) = self.door_poll(resources) {
+            return Some(x);
+        }
+        self.timer_poll(resources)
+    }
+}
+#[allow(dead_code)]
+impl EntryDelay {
+    fn new() -> Self {
+        Self {
+            timer: Timer::new(),
+            knock: KnockCode::new(),
+            wrong_codes: 0,
+            zone: Zone::Door,
+        }
+    }
+    fn button_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
+        entry_button_poll(resources, &mut self.knock, &mut self.wrong_codes)
+    }
+    fn timer_poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
+        if !self.timer.waiting(&CONFIG.entry_delay) {
+            //blocking code here
+            resources.raise_alarm(self.zone);
+            return Some(AlgorithmState::IdleState(Idle {}));
+        }
+        None
+    }
+    fn poll(&mut self, resources: &mut Resources) -> Option<AlgorithmState> {
+        if let Some(x) = self.button_poll(resources) {
+            return Some(x);
         }
+        self.timer_poll(resources)
     }
 }
@@ -0,0 +1,76 @@
+use crate::clock::{Clock, DateTime};
+use crate::config::CONFIG;
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum ScheduleAction {
+    Arm,
+    Disarm,
+}
+
+/// Action at time of day on days of week
+pub struct ScheduleEntry {
+    /// mask of MONDAY..SUNDAY
+    pub days: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub action: ScheduleAction,
+}
+
+#[allow(dead_code)]
+impl ScheduleEntry {
+    pub const MONDAY: u8 = 1 << 0;
+    pub const TUESDAY: u8 = 1 << 1;
+    pub const WEDNESDAY: u8 = 1 << 2;
+    pub const THURSDAY: u8 = 1 << 3;
+    pub const FRIDAY: u8 = 1 << 4;
+    pub const SATURDAY: u8 = 1 << 5;
+    pub const SUNDAY: u8 = 1 << 6;
+    pub const WEEKDAYS: u8 = 0b001_1111;
+    pub const WEEKEND: u8 = 0b110_0000;
+    pub const EVERY_DAY: u8 = 0b111_1111;
+    fn is_at(&self, time: &DateTime) -> bool {
+        self.days & (1 << (time.weekday() - 1)) != 0
+            && self.hour == time.hour
+            && self.minute == time.minute
+    }
+}
+
+/// Weekly schedule from config, checked by wall clock minutes
+pub struct AutoArm {
+    last_minute: Option<u32>,
+}
+
+#[allow(dead_code)]
+impl AutoArm {
+    //minutes missed while device was busy, older ones are skipped
+    const MAX_CATCH_UP: u32 = 10;
+    pub fn new() -> Self {
+        AutoArm { last_minute: None }
+    }
+    ///latest action whose time has come since last call
+    pub fn poll(&mut self) -> Option<ScheduleAction> {
+        //time of day is unknown before network time
+        if !Clock::is_synced() {
+            return None;
+        }
+        let now = Clock::now().to_seconds() / 60;
+        let last = self.last_minute.unwrap_or(now.saturating_sub(1));
+        self.last_minute = Some(now);
+        //clock was moved back, nothing to catch up
+        if now <= last {
+            return None;
+        }
+        let from = (last + 1).max(now.saturating_sub(Self::MAX_CATCH_UP));
+        let mut res = None;
+        for minute in from..=now {
+            let time = DateTime::from_seconds(minute * 60);
+            for entry in CONFIG.schedule.iter() {
+                if entry.is_at(&time) {
+                    res = Some(entry.action);
+                }
+            }
+        }
+        res
+    }
+}
@@ -0,0 +1,111 @@
+use crate::config::CONFIG;
+use crate::door_sensor::{DoorSensor, DoorState};
+use crate::thermometer::Thermometer;
+
+/// Change of sensor guarded around the clock, temperature in tenths of degree
+#[derive(Copy, Clone, PartialEq)]
+pub enum AuxEvent {
+    TemperatureHigh(i16),
+    TemperatureLow(i16),
+    TemperatureNormal(i16),
+    TemperatureLost,
+    Flood,
+    FloodCleared,
+    Smoke,
+    SmokeCleared,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Level {
+    Normal,
+    High,
+    Low,
+}
+
+/// Temperature, flood and smoke sensors, they do not depend on armed state
+pub struct AuxSensors {
+    thermometer: Option<Thermometer>,
+    level: Level,
+    failures: u8,
+    flood: Option<DoorSensor>,
+    smoke: Option<DoorSensor>,
+}
+
+#[allow(dead_code)]
+impl AuxSensors {
+    const MAX_FAILURES: u8 = 3;
+    const HYSTERESIS: i16 = 10;
+    pub fn new(
+        thermometer: Option<Thermometer>,
+        flood: Option<DoorSensor>,
+        smoke: Option<DoorSensor>,
+    ) -> Self {
+        AuxSensors {
+            thermometer,
+            level: Level::Normal,
+            failures: 0,
+            flood,
+            smoke,
+        }
+    }
+    pub fn is_settled(&self) -> bool {
+        self.flood.as_ref().is_none_or(|x| x.is_settled())
+            && self.smoke.as_ref().is_none_or(|x| x.is_settled())
+    }
+    fn level(&self, t: i16) -> Level {
+        let high = CONFIG.temperature_high.map(|x| x * 10);
+        let low = CONFIG.temperature_low.map(|x| x * 10);
+        //stay in alert until temperature is back by hysteresis
+        match (self.level, high, low) {
+            (Level::High, Some(high), _) if t > high - Self::HYSTERESIS => Level::High,
+            (Level::Low, _, Some(low)) if t < low + Self::HYSTERESIS => Level::Low,
+            (_, Some(high), _) if t >= high => Level::High,
+            (_, _, Some(low)) if t <= low => Level::Low,
+            _ => Level::Normal,
+        }
+    }
+    fn temperature_poll(&mut self) -> Option<AuxEvent> {
+        match self.thermometer.as_mut()?.poll()? {
+            Ok(t) => {
+                self.failures = 0;
+                let level = self.level(t);
+                if level == self.level {
+                    return None;
+                }
+                self.level = level;
+                Some(match level {
+                    Level::High => AuxEvent::TemperatureHigh(t),
+                    Level::Low => AuxEvent::TemperatureLow(t),
+                    Level::Normal => AuxEvent::TemperatureNormal(t),
+                })
+            }
+            Err(_) => {
+                //reported once, when failures reach limit
+                if self.failures < Self::MAX_FAILURES {
+                    self.failures += 1;
+                    if self.failures == Self::MAX_FAILURES {
+                        return Some(AuxEvent::TemperatureLost);
+                    }
+                }
+                None
+            }
+        }
+    }
+    ///one event per call. Flood or smoke active already at power up is reported too,
+    ///inputs start from normal state
+    pub fn poll(&mut self) -> Option<AuxEvent> {
+        if let Some(state) = self.flood.as_mut().and_then(|x| x.state()) {
+            return Some(match state {
+                DoorState::Opened => AuxEvent::Flood,
+                _ => AuxEvent::FloodCleared,
+            });
+        }
+        if let Some(state) = self.smoke.as_mut().and_then(|x| x.state()) {
+            return Some(match state {
+                DoorState::Opened => AuxEvent::Smoke,
+                _ => AuxEvent::SmokeCleared,
+            });
+        }
+        self.temperature_poll()
+    }
+}
@@ -1,46 +1,178 @@
+use crate::config::CONFIG;
 use crate::hal::gpio::{Input, PullUp, Pxx};
 use crate::timer::{MilliSeconds, Timer};
 use embedded_hal::digital::v2::InputPin;
+use heapless::consts::*;
+use heapless::Vec;
+
+/// What user did with button, reported once
+#[derive(Copy, Clone, PartialEq)]
+pub enum Gesture {
+    Press,
+    Release,
+    Short,
+    Long,
+    Double,
+}
+
+/// Element of knock code
+#[derive(Copy, Clone, PartialEq)]
+pub enum Knock {
+    Short,
+    Long,
+}
+
 pub struct Button {
     pin: Pxx<Input<PullUp>>,
     counter: u8,
     is_default_high: bool,
     timer: Timer,
+    is_down: bool,
+    is_long: bool,
+    clicks: u8,
+    hold_timer: Timer,
+    pending: Option<Gesture>,
 }
 
+#[allow(dead_code)]
 impl Button {
     const MAX_COUNT: u8 = 3;
-    const TIMEOUT: MilliSeconds = MilliSeconds(25_u16);
+    const TIMEOUT: MilliSeconds = MilliSeconds(25);
+    //second press within this time after release is double press
+    const DOUBLE_GAP: MilliSeconds = MilliSeconds(300);
     pub fn new(pin: Pxx<Input<PullUp>>, is_default_high: bool) -> Self {
         Button {
             pin,
             counter: Self::MAX_COUNT,
             is_default_high,
             timer: Timer::new(),
+            is_down: false,
+            is_long: false,
+            clicks: 0,
+            hold_timer: Timer::new(),
+            pending: None,
         }
     }
-    pub fn is_pressed(&mut self) -> Option<bool> {
+    pub fn is_released(&self) -> bool {
+        match self.pin.is_high() {
+            Ok(is_high) => is_high == self.is_default_high,
+            _ => true,
+        }
+    }
+    ///nothing is pressed and no gesture is being recognized
+    pub fn is_idle(&self) -> bool {
+        self.is_released() && !self.is_down && self.clicks == 0 && self.pending.is_none()
+    }
+    pub fn poll(&mut self) -> Option<Gesture> {
+        if let Some(x) = self.pending.take() {
+            return Some(x);
+        }
         if !self.timer.every(Self::TIMEOUT) {
             return None;
         }
-        match self.pin.is_high() {
-            //unpressed
-            Ok(is_high) if is_high == self.is_default_high => {
-                self.counter = Self::MAX_COUNT;
-                None
+        let is_pressed = match self.pin.is_high() {
+            Ok(is_high) => is_high != self.is_default_high,
+            //other
+            _ => return None,
+        };
+        if is_pressed == self.is_down {
+            self.counter = Self::MAX_COUNT;
+            return self.hold_poll();
+        }
+        //debounce both edges
+        self.counter -= 1;
+        if self.counter > 0 {
+            return None;
+        }
+        self.counter = Self::MAX_COUNT;
+        self.is_down = is_pressed;
+        self.hold_timer.reset();
+        if is_pressed {
+            self.is_long = false;
+            return Some(Gesture::Press);
+        }
+        if !self.is_long {
+            self.clicks += 1;
+            if self.clicks == 2 {
+                self.clicks = 0;
+                self.pending = Some(Gesture::Double);
+            }
+        }
+        Some(Gesture::Release)
+    }
+    fn hold_poll(&mut self) -> Option<Gesture> {
+        if self.is_down {
+            if !self.is_long && !self.hold_timer.waiting(&CONFIG.long_press) {
+                //reported while still held, user knows when to release
+                self.is_long = true;
+                self.clicks = 0;
+                return Some(Gesture::Long);
+            }
+        } else if self.clicks == 1 && !self.hold_timer.waiting(&Self::DOUBLE_GAP) {
+            self.clicks = 0;
+            return Some(Gesture::Short);
+        }
+        None
+    }
+}
+
+/// Collects short and long presses until pause and compares them with code
+pub struct KnockCode {
+    entered: Vec<Knock, U16>,
+    is_overflow: bool,
+    is_holding: bool,
+    after_long: bool,
+    timer: Timer,
+}
+
+#[allow(dead_code)]
+impl KnockCode {
+    const PAUSE: MilliSeconds = MilliSeconds(2000);
+    pub fn new() -> Self {
+        KnockCode {
+            entered: Vec::new(),
+            is_overflow: false,
+            is_holding: false,
+            after_long: false,
+            timer: Timer::new(),
+        }
+    }
+    ///feed gestures of button, Some(is_right) after pause which ends input
+    pub fn poll(&mut self, gesture: Option<Gesture>, code: &[Knock]) -> Option<bool> {
+        match gesture {
+            Some(Gesture::Press) => {
+                self.is_holding = true;
+                self.timer.reset();
             }
-            //pressed
-            Ok(_) => {
-                if self.counter > 0 {
-                    self.counter = self.counter - 1;
-                    if self.counter == 0 {
-                        return Some(true);
-                    }
+            Some(Gesture::Long) => {
+                self.push(Knock::Long);
+                self.after_long = true;
+            }
+            Some(Gesture::Release) => {
+                //release after long press is not a knock itself
+                if !self.after_long {
+                    self.push(Knock::Short);
                 }
-                None
+                self.after_long = false;
+                self.is_holding = false;
             }
-            //other
-            _ => None,
+            Some(_) => {}
+            None => {
+                if !self.entered.is_empty() && !self.is_holding && !self.timer.waiting(&Self::PAUSE)
+                {
+                    let is_right = !self.is_overflow && &self.entered[..] == code;
+                    self.entered.clear();
+                    self.is_overflow = false;
+                    return Some(is_right);
+                }
+            }
+        }
+        None
+    }
+    fn push(&mut self, knock: Knock) {
+        if self.entered.push(knock).is_err() {
+            self.is_overflow = true;
         }
+        self.timer.reset();
     }
 }
@@ -0,0 +1,153 @@
+use crate::hal::{
+    gpio::{gpioa::PA6, Alternate, PushPull},
+    pac::TIM3,
+    pwm::{Channel, Pwm, C1},
+    time::U32Ext,
+    timer::Tim3NoRemap,
+};
+use crate::hardware::system_timer::SystemTimer;
+use crate::indication::IndicationState;
+use core::cell::RefCell;
+use cortex_m::interrupt::Mutex;
+use embedded_hal::Pwm as _;
+
+type BuzzerPwm = Pwm<TIM3, Tim3NoRemap, C1, PA6<Alternate<PushPull>>>;
+
+/// Tone of melody, freq_hz = 0 is pause
+#[derive(Copy, Clone)]
+pub struct Note {
+    pub freq_hz: u16,
+    pub ms: u16,
+}
+
+const fn tone(freq_hz: u16, ms: u16) -> Note {
+    Note { freq_hz, ms }
+}
+
+pub static MELODY_ARMED: [Note; 3] = [tone(1000, 80), tone(0, 40), tone(2000, 150)];
+pub static MELODY_DISARMED: [Note; 3] = [tone(2000, 80), tone(0, 40), tone(1000, 150)];
+pub static MELODY_EXIT_DELAY: [Note; 2] = [tone(2000, 100), tone(0, 900)];
+pub static MELODY_EXIT_DELAY_ENDING: [Note; 2] = [tone(2000, 100), tone(0, 150)];
+pub static MELODY_ENTRY_DELAY: [Note; 2] = [tone(2500, 150), tone(0, 150)];
+pub static MELODY_ERROR: [Note; 7] = [
+    tone(500, 200),
+    tone(0, 200),
+    tone(500, 200),
+    tone(0, 200),
+    tone(500, 200),
+    tone(0, 200),
+    tone(0, 4000),
+];
+pub static MELODY_KEY: [Note; 1] = [tone(3000, 30)];
+pub static MELODY_TAMPER: [Note; 2] = [tone(1500, 250), tone(2500, 250)];
+
+struct Sequencer {
+    pwm: BuzzerPwm,
+    melody: &'static [Note],
+    repeat: bool,
+    index: usize,
+    remaining_ms: u16,
+}
+
+impl Sequencer {
+    fn start_note(&mut self) {
+        let note = self.melody[self.index];
+        self.remaining_ms = note.ms;
+        if note.freq_hz == 0 {
+            self.pwm.disable(Channel::C1);
+        } else {
+            self.pwm.set_period((note.freq_hz as u32).hz());
+            let duty = self.pwm.get_max_duty() / 2;
+            self.pwm.set_duty(Channel::C1, duty);
+            self.pwm.enable(Channel::C1);
+        }
+    }
+    fn tick(&mut self) {
+        if self.melody.is_empty() {
+            return;
+        }
+        if self.remaining_ms > 0 {
+            self.remaining_ms -= 1;
+            return;
+        }
+        self.index += 1;
+        if self.index >= self.melody.len() {
+            if !self.repeat {
+                self.melody = &[];
+                self.pwm.disable(Channel::C1);
+                return;
+            }
+            self.index = 0;
+        }
+        self.start_note();
+    }
+}
+
+static _BUZZER: Mutex<RefCell<Option<Sequencer>>> = Mutex::new(RefCell::new(None));
+
+/// Piezo buzzer on TIM3 PWM, melodies are played from system timer interrupt
+pub struct Buzzer {}
+#[allow(dead_code)]
+impl Buzzer {
+    pub fn init(mut pwm: BuzzerPwm) {
+        pwm.disable(Channel::C1);
+        cortex_m::interrupt::free(|cs| {
+            *_BUZZER.borrow(cs).borrow_mut() = Some(Sequencer {
+                pwm,
+                melody: &[],
+                repeat: false,
+                index: 0,
+                remaining_ms: 0,
+            })
+        });
+        SystemTimer::add_tick_hook(Buzzer::tick);
+    }
+    pub fn play(melody: &'static [Note], repeat: bool) {
+        cortex_m::interrupt::free(|cs| {
+            if let Some(buzzer) = _BUZZER.borrow(cs).borrow_mut().as_mut() {
+                buzzer.melody = melody;
+                buzzer.repeat = repeat;
+                buzzer.index = 0;
+                if !melody.is_empty() {
+                    buzzer.start_note();
+                } else {
+                    buzzer.pwm.disable(Channel::C1);
+                }
+            }
+        });
+    }
+    pub fn stop() {
+        Buzzer::play(&[], false);
+    }
+    pub fn is_playing() -> bool {
+        cortex_m::interrupt::free(|cs| match _BUZZER.borrow(cs).borrow().as_ref() {
+            Some(buzzer) => !buzzer.melody.is_empty(),
+            None => false,
+        })
+    }
+    ///sound feedback for indication state change, same as leds
+    pub fn on_transition(from: IndicationState, to: IndicationState) {
+        match to {
+            IndicationState::ExitDelay => Buzzer::play(&MELODY_EXIT_DELAY, true),
+            IndicationState::EntryDelay => Buzzer::play(&MELODY_ENTRY_DELAY, true),
+            IndicationState::Armed => Buzzer::play(&MELODY_ARMED, false),
+            IndicationState::Error => Buzzer::play(&MELODY_ERROR, false),
+            IndicationState::Tamper => Buzzer::play(&MELODY_TAMPER, true),
+            IndicationState::Idle | IndicationState::IdleDoorClosed => match from {
+                IndicationState::Armed
+                | IndicationState::EntryDelay
+                | IndicationState::ExitDelay
+                | IndicationState::ReadyToArm => Buzzer::play(&MELODY_DISARMED, false),
+                _ => Buzzer::stop(),
+            },
+            _ => Buzzer::stop(),
+        }
+    }
+    fn tick() {
+        cortex_m::interrupt::free(|cs| {
+            if let Some(buzzer) = _BUZZER.borrow(cs).borrow_mut().as_mut() {
+                buzzer.tick();
+            }
+        });
+    }
+}
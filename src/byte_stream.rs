@@ -0,0 +1,11 @@
+/// Bidirectional stream of bytes over some connection, e.g. tcp over gprs
+#[allow(dead_code)]
+pub trait ByteStream {
+    type Error;
+    ///send all bytes, blocking
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+    ///wait a short time for data and copy it into buf, 0 if nothing came. Blocking,
+    ///sim900 listens about one second
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+    fn is_connected(&self) -> bool;
+}
@@ -0,0 +1,155 @@
+use crate::hal::pac::RTC;
+use crate::hardware::backup_registers::{BackupRegister, BackupRegisters};
+use crate::hardware::rtc_clock::RtcClock;
+use core::fmt;
+
+const SECONDS_IN_DAY: u32 = 86_400;
+const BASE_YEAR: u16 = 2000;
+const SYNCED_MARK: u16 = 0x5A5A;
+
+/// Wall-clock local time, counted from 2000-01-01 00:00:00
+#[derive(Copy, Clone, PartialEq, PartialOrd)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+fn is_leap(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn parse_two_digits(s: &str) -> Option<u8> {
+    let b = s.as_bytes();
+    if b.len() != 2 || !b[0].is_ascii_digit() || !b[1].is_ascii_digit() {
+        return None;
+    }
+    Some((b[0] - b'0') * 10 + (b[1] - b'0'))
+}
+
+#[allow(dead_code)]
+impl DateTime {
+    pub fn from_seconds(seconds: u32) -> Self {
+        let mut days = seconds / SECONDS_IN_DAY;
+        let rest = seconds % SECONDS_IN_DAY;
+        let mut year = BASE_YEAR;
+        loop {
+            let year_days = if is_leap(year) { 366 } else { 365 };
+            if days < year_days {
+                break;
+            }
+            days -= year_days;
+            year += 1;
+        }
+        let mut month = 1;
+        loop {
+            let month_days = days_in_month(year, month) as u32;
+            if days < month_days {
+                break;
+            }
+            days -= month_days;
+            month += 1;
+        }
+        DateTime {
+            year,
+            month,
+            day: days as u8 + 1,
+            hour: (rest / 3600) as u8,
+            minute: (rest % 3600 / 60) as u8,
+            second: (rest % 60) as u8,
+        }
+    }
+
+    pub fn to_seconds(self) -> u32 {
+        let mut days: u32 = 0;
+        for year in BASE_YEAR..self.year {
+            days += if is_leap(year) { 366 } else { 365 };
+        }
+        for month in 1..self.month {
+            days += days_in_month(self.year, month) as u32;
+        }
+        days += self.day as u32 - 1;
+        days * SECONDS_IN_DAY
+            + self.hour as u32 * 3600
+            + self.minute as u32 * 60
+            + self.second as u32
+    }
+
+    /// 1 - monday .. 7 - sunday
+    pub fn weekday(&self) -> u8 {
+        //2000-01-01 was saturday
+        ((self.to_seconds() / SECONDS_IN_DAY + 5) % 7) as u8 + 1
+    }
+
+    /// parse modem time "yy/MM/dd,hh:mm:ss+zz", zone is dropped - modem already reports local time
+    pub fn parse(s: &str) -> Option<Self> {
+        if s.len() < 17 {
+            return None;
+        }
+        let year = parse_two_digits(s.get(0..2)?)?;
+        let res = DateTime {
+            year: BASE_YEAR + year as u16,
+            month: parse_two_digits(s.get(3..5)?)?,
+            day: parse_two_digits(s.get(6..8)?)?,
+            hour: parse_two_digits(s.get(9..11)?)?,
+            minute: parse_two_digits(s.get(12..14)?)?,
+            second: parse_two_digits(s.get(15..17)?)?,
+        };
+        if res.is_valid() {
+            Some(res)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.month >= 1
+            && self.month <= 12
+            && self.day >= 1
+            && self.day <= days_in_month(self.year, self.month)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:02}.{:02}.{:04} {:02}:{:02}:{:02}",
+            self.day, self.month, self.year, self.hour, self.minute, self.second
+        )
+    }
+}
+
+pub struct Clock {}
+#[allow(dead_code)]
+impl Clock {
+    pub fn init_system(rtc: RTC) {
+        RtcClock::init(rtc);
+    }
+    pub fn now() -> DateTime {
+        DateTime::from_seconds(RtcClock::counter())
+    }
+    pub fn set(time: DateTime) {
+        RtcClock::set_counter(time.to_seconds());
+        BackupRegisters::write(BackupRegister::ClockSynced, SYNCED_MARK);
+    }
+    ///time was received from network at least once since backup domain power up
+    pub fn is_synced() -> bool {
+        BackupRegisters::read(BackupRegister::ClockSynced) == SYNCED_MARK
+    }
+}
@@ -0,0 +1,230 @@
+#[allow(unused_imports)]
+use crate::auto_arm::{ScheduleAction, ScheduleEntry};
+use crate::button::Knock;
+use crate::siren::Pattern;
+use crate::sms_template::Language;
+use crate::timer::{Hours, MilliSeconds, Seconds};
+
+/// Resistor divider in front of ADC input
+pub struct VoltageDivider {
+    pub top_ohm: u32,
+    pub bottom_ohm: u32,
+}
+
+impl VoltageDivider {
+    ///voltage before divider by voltage on ADC pin
+    pub fn input_mv(&self, adc_mv: u16) -> u16 {
+        let mv = adc_mv as u32 * (self.top_ohm + self.bottom_ohm) / self.bottom_ohm;
+        if mv > u16::MAX as u32 {
+            u16::MAX
+        } else {
+            mv as u16
+        }
+    }
+}
+
+/// Sampling of contact sensor
+pub struct SensorSampling {
+    /// time between samples
+    pub period: MilliSeconds,
+    /// same samples in a row to accept new state
+    pub count: u8,
+    /// level of digital input when contact is open
+    pub open_high: bool,
+}
+
+/// Access point of operator for gprs
+pub struct Apn {
+    pub name: &'static str,
+    pub user: &'static str,
+    pub password: &'static str,
+}
+
+/// Device settings, fixed at build time
+pub struct Config {
+    /// stop mode between polls when nothing is going on, wake by button/door/rtc
+    pub low_power: bool,
+    /// number for notifications, international format
+    pub phone: &'static str,
+    /// external supply (mains adapter) on PA0
+    pub supply_divider: VoltageDivider,
+    /// backup battery on PA1
+    pub battery_divider: VoltageDivider,
+    /// supply below this means mains is lost
+    pub mains_lost_mv: u16,
+    pub battery_low_mv: u16,
+    /// voltage must go back over threshold by this value to restore state
+    pub voltage_hysteresis_mv: u16,
+    /// send sms with panic/fault record after restart
+    pub crash_sms: bool,
+    /// door loop with end-of-line resistors on PB0 instead of plain contact on PB12
+    pub door_eol: bool,
+    /// pull-up from 3.3V to zone input
+    pub eol_pull_up_ohm: u32,
+    /// each of two end-of-line resistors
+    pub eol_resistor_ohm: u32,
+    /// siren sounds this long on alarm, zero disables siren
+    pub siren_duration: Seconds,
+    pub siren_pattern: Pattern,
+    /// short siren beeps on arm and disarm
+    pub arm_chirp: bool,
+    /// buzzer on PA6 follows indication
+    pub buzzer: bool,
+    /// after door is closed device waits this long before arming, zero arms at once
+    pub exit_delay: Seconds,
+    /// time to disarm after door is opened, zero raises alarm at once
+    pub entry_delay: Seconds,
+    /// signal quality (AT+CSQ rssi, 0..31) below this is an error
+    pub signal_min: u8,
+    /// operator ussd request for balance, e.g. Some("#100#"), None disables balance check
+    pub balance_ussd: Option<&'static str>,
+    /// balance below this is reported as warning, device still can be armed
+    pub balance_min: i32,
+    /// hold time of long press, long press is needed to disarm
+    pub long_press: MilliSeconds,
+    /// knock code to disarm instead of long press, empty - long press disarms
+    pub disarm_code: &'static [Knock],
+    /// wrong knock codes before alarm
+    pub disarm_attempts: u8,
+    /// columns of matrix keypad: 3 or 4, 0 - no keypad
+    pub keypad_columns: u8,
+    /// codes to arm and disarm from keypad, entered with '#'
+    pub user_codes: &'static [&'static str],
+    /// disarms as usual but silently sends sms
+    pub duress_code: Option<&'static str>,
+    /// wrong codes before keypad is locked
+    pub keypad_attempts: u8,
+    pub keypad_lockout: Seconds,
+    /// iButton reader on PA8 (1-Wire)
+    pub ibutton: bool,
+    /// serial numbers of enrolled keys as printed on them, without family code and crc
+    pub ibutton_keys: &'static [u64],
+    pub door_sampling: SensorSampling,
+    pub tamper_sampling: SensorSampling,
+    /// PIR detector relay output on PB1
+    pub pir: bool,
+    pub pir_sampling: SensorSampling,
+    /// pulses within window to trip motion zone
+    pub pir_pulses: u8,
+    pub pir_window: Seconds,
+    /// detector is ignored after power on
+    pub pir_warm_up: Seconds,
+    /// alarm only if door and motion zones both trip within window
+    pub cross_zoning: bool,
+    pub cross_zone_window: Seconds,
+    /// DS18B20 on PA7 (1-Wire)
+    pub thermometer: bool,
+    /// alert thresholds in degrees, None disables
+    pub temperature_high: Option<i16>,
+    pub temperature_low: Option<i16>,
+    /// flood contact on PB15
+    pub flood: bool,
+    pub flood_sampling: SensorSampling,
+    /// smoke/fire loop with relay output on PB7
+    pub smoke: bool,
+    pub smoke_sampling: SensorSampling,
+    /// weekly auto arm and disarm by network time, e.g.
+    /// ScheduleEntry { days: ScheduleEntry::WEEKDAYS, hour: 20, minute: 0, action: ScheduleAction::Arm }
+    pub schedule: &'static [ScheduleEntry],
+    /// period of status sms, e.g. Some(Hours(24)) daily or Some(Hours(168)) weekly. None disables it
+    pub heartbeat: Option<Hours>,
+    /// language of sms templates
+    pub language: Language,
+    /// sms on every arm and disarm
+    pub arm_sms: bool,
+    /// alarm sms requests delivery report, not delivered one is resent and then phone is called
+    pub delivery_report: bool,
+    /// time to wait for delivery report
+    pub delivery_timeout: Seconds,
+    /// resends of not delivered alarm sms before call
+    pub delivery_retries: u8,
+    /// ringing time of call when alarm sms is not delivered
+    pub call_duration: Seconds,
+    /// gprs access point, None disables packet data
+    #[allow(dead_code)]
+    pub apn: Option<Apn>,
+}
+
+pub static CONFIG: Config = Config {
+    low_power: true,
+    phone: "79142123472",
+    supply_divider: VoltageDivider {
+        top_ohm: 10_000,
+        bottom_ohm: 2_200,
+    },
+    battery_divider: VoltageDivider {
+        top_ohm: 10_000,
+        bottom_ohm: 10_000,
+    },
+    mains_lost_mv: 10_000,
+    battery_low_mv: 3_600,
+    voltage_hysteresis_mv: 300,
+    crash_sms: true,
+    door_eol: false,
+    eol_pull_up_ohm: 4_700,
+    eol_resistor_ohm: 4_700,
+    siren_duration: Seconds(180),
+    siren_pattern: Pattern::CONTINUOUS,
+    arm_chirp: true,
+    buzzer: true,
+    exit_delay: Seconds(0),
+    entry_delay: Seconds(0),
+    signal_min: 5,
+    balance_ussd: None,
+    balance_min: 20,
+    long_press: MilliSeconds(1500),
+    disarm_code: &[],
+    disarm_attempts: 3,
+    keypad_columns: 0,
+    user_codes: &["1234"],
+    duress_code: None,
+    keypad_attempts: 5,
+    keypad_lockout: Seconds(300),
+    ibutton: false,
+    ibutton_keys: &[],
+    door_sampling: SensorSampling {
+        period: MilliSeconds(250),
+        count: 3,
+        open_high: true,
+    },
+    tamper_sampling: SensorSampling {
+        period: MilliSeconds(1000),
+        count: 3,
+        open_high: true,
+    },
+    pir: false,
+    pir_sampling: SensorSampling {
+        period: MilliSeconds(50),
+        count: 2,
+        open_high: true,
+    },
+    pir_pulses: 2,
+    pir_window: Seconds(30),
+    pir_warm_up: Seconds(60),
+    cross_zoning: false,
+    cross_zone_window: Seconds(60),
+    thermometer: false,
+    temperature_high: Some(50),
+    temperature_low: Some(5),
+    flood: false,
+    flood_sampling: SensorSampling {
+        period: MilliSeconds(1000),
+        count: 3,
+        open_high: true,
+    },
+    smoke: false,
+    smoke_sampling: SensorSampling {
+        period: MilliSeconds(500),
+        count: 3,
+        open_high: true,
+    },
+    schedule: &[],
+    heartbeat: None,
+    language: Language::Russian,
+    arm_sms: false,
+    delivery_report: false,
+    delivery_timeout: Seconds(90),
+    delivery_retries: 1,
+    call_duration: Seconds(30),
+    apn: None,
+};
@@ -0,0 +1,152 @@
+use core::fmt::{self, Write};
+use core::mem::MaybeUninit;
+use core::panic::PanicInfo;
+use core::ptr;
+use cortex_m_rt::{exception, ExceptionFrame};
+
+const MAGIC: u32 = 0xDEAD_C0DE;
+const MESSAGE_SIZE: usize = 80;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum CrashKind {
+    Panic,
+    HardFault,
+}
+
+/// Short summary of crash, small enough for event log
+#[derive(Copy, Clone, PartialEq)]
+pub struct CrashInfo {
+    pub kind: CrashKind,
+    /// program counter on hard fault, 0 on panic
+    pub pc: u32,
+    /// source line of panic, 0 on hard fault
+    pub line: u32,
+}
+
+/// Post-mortem info, lives in RAM which is not cleared on startup
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct CrashRecord {
+    magic: u32,
+    kind: u32,
+    line: u32,
+    message_len: u32,
+    message: [u8; MESSAGE_SIZE],
+    /// r0, r1, r2, r3, r12, lr, pc, xpsr on hard fault
+    pub registers: [u32; 8],
+    checksum: u32,
+}
+
+#[link_section = ".uninit.CRASH_RECORD"]
+static mut CRASH_RECORD: MaybeUninit<CrashRecord> = MaybeUninit::uninit();
+
+struct MessageWriter<'a> {
+    buf: &'a mut [u8; MESSAGE_SIZE],
+    len: usize,
+}
+
+impl<'a> Write for MessageWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        //message is cut, but only on char boundary
+        for c in s.chars() {
+            let mut tmp = [0_u8; 4];
+            let bytes = c.encode_utf8(&mut tmp).as_bytes();
+            if self.len + bytes.len() > MESSAGE_SIZE {
+                return Err(fmt::Error);
+            }
+            self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+            self.len += bytes.len();
+        }
+        Ok(())
+    }
+}
+
+#[allow(dead_code)]
+impl CrashRecord {
+    fn new(kind: CrashKind) -> Self {
+        CrashRecord {
+            magic: MAGIC,
+            kind: kind as u32,
+            line: 0,
+            message_len: 0,
+            message: [0; MESSAGE_SIZE],
+            registers: [0; 8],
+            checksum: 0,
+        }
+    }
+    fn calc_checksum(&self) -> u32 {
+        let mut sum = self.magic ^ self.kind ^ self.line ^ self.message_len;
+        for b in self.message.iter() {
+            sum = sum.rotate_left(3) ^ *b as u32;
+        }
+        for r in self.registers.iter() {
+            sum = sum.rotate_left(3) ^ *r;
+        }
+        sum
+    }
+    fn save(mut self) {
+        self.checksum = self.calc_checksum();
+        unsafe { ptr::write_volatile(ptr::addr_of_mut!(CRASH_RECORD) as *mut CrashRecord, self) };
+    }
+    pub fn kind(&self) -> CrashKind {
+        if self.kind == CrashKind::HardFault as u32 {
+            CrashKind::HardFault
+        } else {
+            CrashKind::Panic
+        }
+    }
+    pub fn message(&self) -> &str {
+        core::str::from_utf8(&self.message[..self.message_len as usize]).unwrap_or("")
+    }
+    pub fn pc(&self) -> u32 {
+        self.registers[6]
+    }
+    pub fn info(&self) -> CrashInfo {
+        let kind = self.kind();
+        CrashInfo {
+            kind,
+            pc: match kind {
+                CrashKind::HardFault => self.pc(),
+                CrashKind::Panic => 0,
+            },
+            line: self.line,
+        }
+    }
+}
+
+pub fn save_panic(info: &PanicInfo) {
+    let mut record = CrashRecord::new(CrashKind::Panic);
+    let mut writer = MessageWriter {
+        buf: &mut record.message,
+        len: 0,
+    };
+    if let Some(location) = info.location() {
+        record.line = location.line();
+        let _ = write!(writer, "{}:{} ", location.file(), location.line());
+    }
+    let _ = write!(writer, "{}", info.message());
+    record.message_len = writer.len as u32;
+    record.save();
+}
+
+///record left by previous run, it is removed after reading
+pub fn take() -> Option<CrashRecord> {
+    let p = ptr::addr_of_mut!(CRASH_RECORD) as *mut CrashRecord;
+    let record = unsafe { ptr::read_volatile(p) };
+    unsafe { ptr::write_volatile(ptr::addr_of_mut!((*p).magic), 0) };
+    if record.magic != MAGIC
+        || record.message_len as usize > MESSAGE_SIZE
+        || record.checksum != record.calc_checksum()
+    {
+        return None;
+    }
+    Some(record)
+}
+
+#[exception]
+fn HardFault(ef: &ExceptionFrame) -> ! {
+    let mut record = CrashRecord::new(CrashKind::HardFault);
+    record.registers = [ef.r0, ef.r1, ef.r2, ef.r3, ef.r12, ef.lr, ef.pc, ef.xpsr];
+    record.save();
+    cortex_m::peripheral::SCB::sys_reset();
+}
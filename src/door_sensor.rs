@@ -1,58 +1,133 @@
-use crate::hal::gpio::{Input, PullUp, Pxx};
-use crate::timer::{MilliSeconds, Timer};
+use crate::config::{SensorSampling, CONFIG};
+use crate::hal::gpio::{gpiob::PB0, Analog as AnalogMode, Input, PullUp, Pxx};
+use crate::hardware::analog::Analog;
+use crate::hardware::low_power::LowPower;
+use crate::timer::Timer;
 use embedded_hal::digital::v2::InputPin;
-pub struct DoorSensor {
-    pin: Pxx<Input<PullUp>>,
-    counter: u8,
-    timer: Timer,
-    is_opened_last_state: bool,
+
+/// How sensor is wired
+pub enum DoorInput {
+    /// contact to ground, level of open contact is configured
+    Digital(Pxx<Input<PullUp>>),
+    /// loop with two end-of-line resistors measured by ADC:
+    /// closed contact shorts one of them, so cut and shorted wire are seen as fault
+    EndOfLine(PB0<AnalogMode>),
 }
+
+#[derive(Copy, Clone, PartialEq)]
 pub enum DoorState {
     Opened,
     Closed,
+    Fault,
+}
+
+pub struct DoorSensor {
+    input: DoorInput,
+    sampling: &'static SensorSampling,
+    //exti line of digital input, its edges catch openings shorter than sampling
+    latch_line: Option<u8>,
+    is_latching: bool,
+    counter: u8,
+    timer: Timer,
+    last_state: DoorState,
+}
+
+///zone loop resistance by pull-up divider and ADC value
+fn classify_end_of_line(raw: u16, max: u16) -> DoorState {
+    if raw >= max {
+        return DoorState::Fault; //cut
+    }
+    let pull_up = CONFIG.eol_pull_up_ohm;
+    let r = CONFIG.eol_resistor_ohm;
+    let zone = pull_up * raw as u32 / (max - raw) as u32;
+    if zone < r / 2 {
+        DoorState::Fault //short
+    } else if zone < r + r / 2 {
+        DoorState::Closed //one resistor
+    } else if zone < 4 * r {
+        DoorState::Opened //two resistors
+    } else {
+        DoorState::Fault //cut
+    }
 }
 
+#[allow(dead_code)]
 impl DoorSensor {
-    const MAX_COUNT: u8 = 3;
-    const TIMEOUT: MilliSeconds = MilliSeconds(1000_u16);
-    pub fn new(pin: Pxx<Input<PullUp>>) -> Self {
-        let mut res = DoorSensor {
-            pin,
-            counter: Self::MAX_COUNT,
+    pub fn new(input: DoorInput, sampling: &'static SensorSampling, latch_line: Option<u8>) -> Self {
+        DoorSensor {
+            input,
+            sampling,
+            latch_line,
+            is_latching: false,
+            counter: sampling.count,
             timer: Timer::new(),
-            is_opened_last_state: true,
-        };
-        res.is_opened_last_state = res.is_open();
-        res
-    }
-    pub fn is_open(&self) -> bool {
-        match self.pin.is_high() {
-            Ok(x) => x,
-            _ => true,
+            //normal state, so contact which is open or faulty at power up is reported
+            //by first state(), e.g. enclosure opened while power was cut
+            last_state: DoorState::Closed,
+        }
+    }
+    pub fn read(&mut self) -> DoorState {
+        match &mut self.input {
+            DoorInput::Digital(pin) => match pin.is_high() {
+                Ok(is_high) if is_high == self.sampling.open_high => DoorState::Opened,
+                Ok(_) => DoorState::Closed,
+                Err(_) => DoorState::Fault,
+            },
+            DoorInput::EndOfLine(pin) => match Analog::read(pin) {
+                Some(raw) => classify_end_of_line(raw, Analog::max_sample()),
+                None => DoorState::Fault,
+            },
+        }
+    }
+    pub fn is_open(&mut self) -> bool {
+        self.read() == DoorState::Opened
+    }
+    ///no change in progress, sensor can be left unsampled for a while
+    pub fn is_settled(&self) -> bool {
+        self.counter == self.sampling.count
+    }
+    ///while latching any edge on closed contact is taken as opening
+    pub fn set_latching(&mut self, is_latching: bool) {
+        if let Some(line) = self.latch_line {
+            //forget edges from before
+            LowPower::take_edge(line);
         }
+        self.is_latching = is_latching;
+    }
+    ///read now and take it as reported, state() does not report it again
+    pub fn settle(&mut self) -> DoorState {
+        self.last_state = self.read();
+        self.counter = self.sampling.count;
+        self.last_state
     }
-    pub fn is_closed(&self) -> bool {
-        !self.is_open()
+    pub fn is_closed(&mut self) -> bool {
+        self.read() == DoorState::Closed
     }
     pub fn state(&mut self) -> Option<DoorState> {
-        if !self.timer.every(Self::TIMEOUT) {
+        if let Some(line) = self.latch_line {
+            if LowPower::take_edge(line) && self.is_latching && self.last_state == DoorState::Closed
+            {
+                //door was opened and closed between samples
+                self.counter = self.sampling.count;
+                self.last_state = DoorState::Opened;
+                return Some(DoorState::Opened);
+            }
+        }
+        if !self.timer.every(self.sampling.period) {
             return None;
         }
-        let is_open = self.is_open();
-        if is_open != self.is_opened_last_state {
+        let state = self.read();
+        if state != self.last_state {
             if self.counter > 0 {
                 self.counter = self.counter - 1;
                 if self.counter == 0 {
-                    self.is_opened_last_state = is_open;
-
-                    return match is_open {
-                        true => Some(DoorState::Opened),
-                        false => Some(DoorState::Closed),
-                    };
+                    self.counter = self.sampling.count;
+                    self.last_state = state;
+                    return Some(state);
                 }
             }
         } else {
-            self.counter = Self::MAX_COUNT;
+            self.counter = self.sampling.count;
         }
         return None;
     }
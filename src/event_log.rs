@@ -0,0 +1,80 @@
+use crate::aux_sensors::AuxEvent;
+use crate::clock::{Clock, DateTime};
+use crate::crash_record::CrashInfo;
+use crate::hardware::reset_cause::ResetCause;
+use crate::ibutton::KeyId;
+use crate::zone::Zone;
+use core::cell::RefCell;
+use cortex_m::interrupt::Mutex;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum EventKind {
+    Reset(ResetCause),
+    Crash(CrashInfo),
+    Alarm(Zone),
+    MainsLost,
+    MainsRestored,
+    BatteryLow,
+    PowerCycledArmed,
+    Tamper,
+    ZoneFault,
+    CodeLockout,
+    Duress,
+    Key(KeyId),
+    UnknownKey(KeyId),
+    Aux(AuxEvent),
+    AutoArm,
+    AutoArmFailed,
+    AutoDisarm,
+    NotDelivered,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone)]
+pub struct Event {
+    pub time: DateTime,
+    pub kind: EventKind,
+}
+
+const SIZE: usize = 16;
+
+struct Log {
+    events: [Option<Event>; SIZE],
+    next: usize,
+}
+
+static _LOG: Mutex<RefCell<Log>> = Mutex::new(RefCell::new(Log {
+    events: [None; SIZE],
+    next: 0,
+}));
+
+/// Last events in RAM, oldest ones are overwritten
+pub struct EventLog {}
+#[allow(dead_code)]
+impl EventLog {
+    pub fn push(kind: EventKind) {
+        let event = Event {
+            time: Clock::now(),
+            kind,
+        };
+        cortex_m::interrupt::free(|cs| {
+            let mut log = _LOG.borrow(cs).borrow_mut();
+            let next = log.next;
+            log.events[next] = Some(event);
+            log.next = (next + 1) % SIZE;
+        });
+    }
+    ///n-th event from the newest one
+    pub fn get(n: usize) -> Option<Event> {
+        if n >= SIZE {
+            return None;
+        }
+        cortex_m::interrupt::free(|cs| {
+            let log = _LOG.borrow(cs).borrow();
+            log.events[(log.next + SIZE - 1 - n) % SIZE]
+        })
+    }
+    pub fn last() -> Option<Event> {
+        EventLog::get(0)
+    }
+}
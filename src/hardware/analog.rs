@@ -0,0 +1,26 @@
+use crate::hal::{adc::Adc, pac::ADC1};
+use crate::utils::global_cell::GlobalCell;
+use embedded_hal::adc::{Channel, OneShot};
+
+static _ADC: GlobalCell<Adc<ADC1>> = GlobalCell::<Adc<ADC1>>::new();
+const VREF_MV: u32 = 3300;
+
+///shared ADC1, channels are owned by their users
+pub struct Analog {}
+#[allow(dead_code)]
+impl Analog {
+    pub fn init(adc: ADC1, apb2: &mut hal::rcc::APB2, clocks: hal::rcc::Clocks) {
+        _ADC.set(Adc::adc1(adc, apb2, clocks));
+    }
+    pub fn read<PIN: Channel<ADC1, ID = u8>>(pin: &mut PIN) -> Option<u16> {
+        nb::block!(_ADC.get().read(pin)).ok()
+    }
+    pub fn max_sample() -> u16 {
+        _ADC.get().max_sample()
+    }
+    ///voltage on pin in millivolts
+    pub fn millivolts<PIN: Channel<ADC1, ID = u8>>(pin: &mut PIN) -> Option<u16> {
+        let max = _ADC.get().max_sample() as u32;
+        Analog::read(pin).map(|raw| (raw as u32 * VREF_MV / max) as u16)
+    }
+}
@@ -0,0 +1,29 @@
+use crate::hal::backup_domain::BackupDomain;
+use crate::utils::global_cell::GlobalCell;
+
+static _BKP: GlobalCell<BackupDomain> = GlobalCell::<BackupDomain>::new();
+
+/// Backup data registers (DR1..DR10), kept while Vdd or Vbat is present
+#[derive(Copy, Clone)]
+pub enum BackupRegister {
+    ClockSynced = 0,
+    HeartbeatLow = 1,
+    HeartbeatHigh = 2,
+}
+
+pub struct BackupRegisters {}
+#[allow(dead_code)]
+impl BackupRegisters {
+    pub fn init(bkp: BackupDomain) {
+        _BKP.set(bkp);
+    }
+    pub fn domain() -> &'static mut BackupDomain {
+        _BKP.get()
+    }
+    pub fn read(reg: BackupRegister) -> u16 {
+        _BKP.get().read_data_register_low(reg as usize)
+    }
+    pub fn write(reg: BackupRegister, value: u16) {
+        _BKP.get().write_data_register_low(reg as usize, value);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::hal::pac::FLASH;
+use core::ptr;
+
+//last two 1K pages of 64K flash, excluded from FLASH region in memory.x
+const PAGES: [u32; 2] = [0x0800_F800, 0x0800_FC00];
+const PAGE_SIZE: u32 = 1024;
+//first word of page in use, sequence number in low half. Never decodes as a record
+const HEADER_MARK: u32 = 0xA5A5_0000;
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xCDEF_89AB;
+const EMPTY: u32 = 0xFFFF_FFFF;
+
+/// Values kept in flash, survive power loss without battery
+#[derive(Copy, Clone, PartialEq)]
+pub enum StorageKey {
+    ArmedIntent = 1,
+}
+const ALL_KEYS: [StorageKey; 1] = [StorageKey::ArmedIntent];
+
+/// Append-only records in one of two flash pages. When page is full, last values are
+/// copied to other page and only then it is marked as used, so power loss keeps old copy.
+/// Record is 32 bit: value in low half, key and inverted key in high half
+pub struct FlashStorage {}
+
+fn encode(key: StorageKey, value: u16) -> u32 {
+    let key = key as u8;
+    value as u32 | (key as u32) << 16 | ((!key) as u32) << 24
+}
+
+fn decode(record: u32) -> Option<(u8, u16)> {
+    let key = (record >> 16) as u8;
+    if (record >> 24) as u8 != !key {
+        return None;
+    }
+    Some((key, record as u16))
+}
+
+fn read_word(page: u32, offset: u32) -> u32 {
+    unsafe { ptr::read_volatile((page + offset) as *const u32) }
+}
+
+fn sequence(page: u32) -> Option<u16> {
+    let header = read_word(page, 0);
+    if header & 0xFFFF_0000 != HEADER_MARK {
+        return None;
+    }
+    Some(header as u16)
+}
+
+///page with newest header, None if storage was never written
+fn active_page() -> Option<u32> {
+    match (sequence(PAGES[0]), sequence(PAGES[1])) {
+        (Some(a), Some(b)) if (b.wrapping_sub(a) as i16) > 0 => Some(PAGES[1]),
+        (Some(_), _) => Some(PAGES[0]),
+        (None, Some(_)) => Some(PAGES[1]),
+        (None, None) => None,
+    }
+}
+
+fn flash() -> &'static crate::hal::pac::flash::RegisterBlock {
+    unsafe { &*FLASH::ptr() }
+}
+
+fn unlock() {
+    let flash = flash();
+    if flash.cr.read().lock().bit_is_set() {
+        flash.keyr.write(|w| unsafe { w.key().bits(KEY1) });
+        flash.keyr.write(|w| unsafe { w.key().bits(KEY2) });
+    }
+}
+
+fn lock() {
+    flash().cr.modify(|_, w| w.lock().set_bit());
+}
+
+fn wait_ready() {
+    while flash().sr.read().bsy().bit_is_set() {}
+}
+
+///wait for end of operation and clear its flags, error if programming or protection failed
+fn finish() -> Result<(), ()> {
+    let flash = flash();
+    wait_ready();
+    let sr = flash.sr.read();
+    let is_failed = sr.pgerr().bit_is_set() || sr.wrprterr().bit_is_set();
+    //flags are cleared by writing one
+    flash
+        .sr
+        .write(|w| w.eop().set_bit().pgerr().set_bit().wrprterr().set_bit());
+    if is_failed {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+fn erase_page(page: u32) -> Result<(), ()> {
+    let flash = flash();
+    wait_ready();
+    flash.cr.modify(|_, w| w.per().set_bit());
+    flash.ar.write(|w| unsafe { w.far().bits(page) });
+    flash.cr.modify(|_, w| w.strt().set_bit());
+    let res = finish();
+    flash.cr.modify(|_, w| w.per().clear_bit());
+    res?;
+    let mut offset = 0;
+    while offset < PAGE_SIZE {
+        if read_word(page, offset) != EMPTY {
+            return Err(());
+        }
+        offset += 4;
+    }
+    Ok(())
+}
+
+fn program_word(page: u32, offset: u32, word: u32) -> Result<(), ()> {
+    let flash = flash();
+    let address = page + offset;
+    //flash is programmed by half words only
+    for (i, half) in [word as u16, (word >> 16) as u16].iter().enumerate() {
+        wait_ready();
+        flash.cr.modify(|_, w| w.pg().set_bit());
+        unsafe { ptr::write_volatile((address + i as u32 * 2) as *mut u16, *half) };
+        let res = finish();
+        flash.cr.modify(|_, w| w.pg().clear_bit());
+        res?;
+    }
+    if read_word(page, offset) != word {
+        return Err(());
+    }
+    Ok(())
+}
+
+///copy last values and new one to other page, old page is left as is until next copy
+fn compact(active: Option<u32>, key: StorageKey, value: u16) -> Result<(), ()> {
+    let page = match active {
+        Some(x) if x == PAGES[0] => PAGES[1],
+        _ => PAGES[0],
+    };
+    let number = active.and_then(sequence).map_or(0, |x| x.wrapping_add(1));
+    erase_page(page)?;
+    let mut offset = 4;
+    for k in ALL_KEYS.iter() {
+        let v = match *k == key {
+            true => Some(value),
+            false => FlashStorage::read(*k),
+        };
+        if let Some(v) = v {
+            program_word(page, offset, encode(*k, v))?;
+            offset += 4;
+        }
+    }
+    //header is written last, until then old page stays active
+    program_word(page, 0, HEADER_MARK | number as u32)
+}
+
+#[allow(dead_code)]
+impl FlashStorage {
+    ///last written value of key
+    pub fn read(key: StorageKey) -> Option<u16> {
+        let page = active_page()?;
+        let mut res = None;
+        let mut offset = 4;
+        while offset < PAGE_SIZE {
+            let record = read_word(page, offset);
+            if record == EMPTY {
+                break;
+            }
+            if let Some((k, value)) = decode(record) {
+                if k == key as u8 {
+                    res = Some(value);
+                }
+            }
+            offset += 4;
+        }
+        res
+    }
+
+    ///false if flash could not be written, old value is kept then
+    pub fn write(key: StorageKey, value: u16) -> bool {
+        if FlashStorage::read(key) == Some(value) {
+            return true;
+        }
+        let active = active_page();
+        unlock();
+        let mut res = Err(());
+        if let Some(page) = active {
+            let mut offset = 4;
+            while offset < PAGE_SIZE && read_word(page, offset) != EMPTY {
+                offset += 4;
+            }
+            if offset < PAGE_SIZE {
+                res = program_word(page, offset, encode(key, value));
+            }
+        }
+        //full page or failed word, fresh copy on other page
+        if res.is_err() {
+            res = compact(active, key, value);
+        }
+        lock();
+        res.is_ok()
+    }
+}
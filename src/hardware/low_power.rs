@@ -0,0 +1,92 @@
+use crate::hal::pac::{interrupt, Interrupt, EXTI, PWR, RTC};
+use crate::hardware::rtc_clock::RtcClock;
+use crate::hardware::system_timer::SystemTimer;
+use crate::timer::Seconds;
+use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::peripheral::SCB;
+
+const EXTI_LINE_RTC_ALARM: u32 = 1 << 17;
+//edges seen on exti lines since they were taken
+static EDGES: AtomicU32 = AtomicU32::new(0);
+
+pub struct LowPower {
+    scb: SCB,
+    pwr: PWR,
+    exti: EXTI,
+}
+#[allow(dead_code)]
+impl LowPower {
+    pub fn new(scb: SCB, pwr: PWR, exti: EXTI) -> Self {
+        //rtc alarm is routed to exti line 17, it is the only way to leave stop mode by time
+        exti.imr
+            .modify(|r, w| unsafe { w.bits(r.bits() | EXTI_LINE_RTC_ALARM) });
+        exti.rtsr
+            .modify(|r, w| unsafe { w.bits(r.bits() | EXTI_LINE_RTC_ALARM) });
+        RtcClock::listen_alarm();
+        unsafe {
+            cortex_m::peripheral::NVIC::unmask(Interrupt::RTCALARM);
+            cortex_m::peripheral::NVIC::unmask(Interrupt::EXTI1);
+            cortex_m::peripheral::NVIC::unmask(Interrupt::EXTI9_5);
+            cortex_m::peripheral::NVIC::unmask(Interrupt::EXTI15_10);
+        }
+        LowPower { scb, pwr, exti }
+    }
+    ///used to configure wake up pins
+    pub fn exti(&self) -> &EXTI {
+        &self.exti
+    }
+    ///true if edge was on exti line since last call
+    pub fn take_edge(line: u8) -> bool {
+        let mask = 1 << line;
+        EDGES.fetch_and(!mask, Ordering::Relaxed) & mask != 0
+    }
+    ///sleep until next interrupt, system timer wakes us every millisecond
+    pub fn sleep(&mut self) {
+        cortex_m::asm::wfi();
+    }
+    ///stop mode: clocks are off, wake on exti pin or rtc alarm.
+    ///System timer is not running here, so it is moved forward by rtc seconds afterwards
+    pub fn stop(&mut self, max: Seconds) {
+        let start = RtcClock::counter();
+        RtcClock::set_alarm(start.wrapping_add(max.0 as u32));
+        //stop mode with regulator in low power
+        self.pwr
+            .cr
+            .modify(|_, w| w.pdds().clear_bit().lpds().set_bit());
+        self.scb.set_sleepdeep();
+        cortex_m::asm::wfi();
+        self.scb.clear_sleepdeep();
+        //after wakeup hsi is used as system clock, same as configured in main
+        let slept = RtcClock::counter().wrapping_sub(start);
+        SystemTimer::inc(slept.saturating_mul(1_000));
+    }
+}
+
+fn clear_exti(mask: u32) {
+    let exti = unsafe { &*EXTI::ptr() };
+    let pending = exti.pr.read().bits() & mask;
+    exti.pr.write(|w| unsafe { w.bits(pending) });
+    EDGES.fetch_or(pending, Ordering::Relaxed);
+}
+
+#[interrupt]
+fn EXTI1() {
+    clear_exti(0x0000_0002);
+}
+
+#[interrupt]
+fn EXTI9_5() {
+    clear_exti(0x0000_03E0);
+}
+
+#[interrupt]
+fn EXTI15_10() {
+    clear_exti(0x0000_FC00);
+}
+
+#[interrupt]
+fn RTCALARM() {
+    let rtc = unsafe { &*RTC::ptr() };
+    rtc.crl.modify(|_, w| w.alrf().clear_bit());
+    clear_exti(EXTI_LINE_RTC_ALARM);
+}
@@ -1,2 +1,10 @@
+pub mod analog;
+pub mod backup_registers;
+pub mod flash_storage;
+pub mod low_power;
+pub mod one_wire;
+pub mod reset_cause;
+pub mod rtc_clock;
 pub mod system_timer;
-pub mod usart_adapter;
\ No newline at end of file
+pub mod usart_adapter;
+pub mod watchdog;
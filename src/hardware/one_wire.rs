@@ -0,0 +1,114 @@
+use crate::hal::gpio::{OpenDrain, Output, Pxx};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+
+pub const CMD_READ_ROM: u8 = 0x33;
+pub const CMD_SKIP_ROM: u8 = 0xCC;
+
+/// Bit-banged 1-Wire master on open drain pin with external pull-up.
+/// Time slots are made with busy loops and interrupts disabled
+pub struct OneWire {
+    pin: Pxx<Output<OpenDrain>>,
+    cycles_per_us: u32,
+}
+
+///Dallas/Maxim crc8, zero for valid data with crc at the end
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        let mut b = *byte;
+        for _ in 0..8 {
+            let mix = (crc ^ b) & 0x01;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8C;
+            }
+            b >>= 1;
+        }
+    }
+    crc
+}
+
+#[allow(dead_code)]
+impl OneWire {
+    pub fn new(mut pin: Pxx<Output<OpenDrain>>, sysclk_hz: u32) -> Self {
+        pin.set_high().unwrap();
+        OneWire {
+            pin,
+            cycles_per_us: sysclk_hz / 1_000_000,
+        }
+    }
+    fn delay_us(&self, us: u32) {
+        cortex_m::asm::delay(us * self.cycles_per_us);
+    }
+    ///true if some device answered with presence pulse
+    pub fn reset(&mut self) -> bool {
+        self.pin.set_low().unwrap();
+        self.delay_us(480);
+        let is_present = cortex_m::interrupt::free(|_| {
+            self.pin.set_high().unwrap();
+            self.delay_us(70);
+            self.pin.is_low().unwrap_or(false)
+        });
+        self.delay_us(410);
+        //bus shorted to ground is not a device
+        is_present && self.pin.is_high().unwrap_or(false)
+    }
+    fn write_bit(&mut self, bit: bool) {
+        cortex_m::interrupt::free(|_| {
+            self.pin.set_low().unwrap();
+            if bit {
+                self.delay_us(6);
+                self.pin.set_high().unwrap();
+                self.delay_us(64);
+            } else {
+                self.delay_us(60);
+                self.pin.set_high().unwrap();
+                self.delay_us(10);
+            }
+        });
+    }
+    fn read_bit(&mut self) -> bool {
+        cortex_m::interrupt::free(|_| {
+            self.pin.set_low().unwrap();
+            self.delay_us(6);
+            self.pin.set_high().unwrap();
+            self.delay_us(9);
+            let bit = self.pin.is_high().unwrap_or(false);
+            self.delay_us(55);
+            bit
+        })
+    }
+    pub fn write_byte(&mut self, byte: u8) {
+        for i in 0..8 {
+            self.write_bit(byte & (1 << i) != 0);
+        }
+    }
+    pub fn read_byte(&mut self) -> u8 {
+        let mut res = 0;
+        for i in 0..8 {
+            if self.read_bit() {
+                res |= 1 << i;
+            }
+        }
+        res
+    }
+    pub fn read_bytes(&mut self, buf: &mut [u8]) {
+        for b in buf.iter_mut() {
+            *b = self.read_byte();
+        }
+    }
+    ///rom code of single device on bus: family, serial, crc. Little endian
+    pub fn read_rom(&mut self) -> Option<u64> {
+        if !self.reset() {
+            return None;
+        }
+        self.write_byte(CMD_READ_ROM);
+        let mut rom = [0u8; 8];
+        self.read_bytes(&mut rom);
+        //all zeros passes crc, but it is shorted data line
+        if crc8(&rom) != 0 || rom[0] == 0 {
+            return None;
+        }
+        Some(u64::from_le_bytes(rom))
+    }
+}
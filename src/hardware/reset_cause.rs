@@ -0,0 +1,32 @@
+use crate::hal::pac::RCC;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ResetCause {
+    PowerOn, //also brown-out, they are not distinguished by hardware
+    Pin,
+    Software,
+    IndependentWatchdog,
+    WindowWatchdog,
+    LowPower,
+}
+
+///read flags from RCC_CSR and clear them, so next reset is reported correctly
+pub fn read_and_clear() -> ResetCause {
+    let rcc = unsafe { &*RCC::ptr() };
+    let csr = rcc.csr.read();
+    let cause = if csr.iwdgrstf().bit_is_set() {
+        ResetCause::IndependentWatchdog
+    } else if csr.wwdgrstf().bit_is_set() {
+        ResetCause::WindowWatchdog
+    } else if csr.sftrstf().bit_is_set() {
+        ResetCause::Software
+    } else if csr.lpwrrstf().bit_is_set() {
+        ResetCause::LowPower
+    } else if csr.porrstf().bit_is_set() {
+        ResetCause::PowerOn
+    } else {
+        ResetCause::Pin
+    };
+    rcc.csr.modify(|_, w| w.rmvf().set_bit());
+    cause
+}
@@ -0,0 +1,27 @@
+use crate::hal::{pac::RTC, rtc::Rtc};
+use crate::hardware::backup_registers::BackupRegisters;
+use crate::utils::global_cell::GlobalCell;
+
+static _RTC: GlobalCell<Rtc> = GlobalCell::<Rtc>::new();
+
+///RTC in backup domain clocked from LSE, counts seconds
+pub struct RtcClock {}
+#[allow(dead_code)]
+impl RtcClock {
+    pub fn init(rtc: RTC) {
+        //counter is not touched, so time survives reset while Vbat is present
+        _RTC.set(Rtc::rtc(rtc, BackupRegisters::domain()));
+    }
+    pub fn counter() -> u32 {
+        _RTC.get().current_time()
+    }
+    pub fn set_counter(value: u32) {
+        _RTC.get().set_time(value);
+    }
+    pub fn set_alarm(value: u32) {
+        _RTC.get().set_alarm(value);
+    }
+    pub fn listen_alarm() {
+        _RTC.get().listen_alarm();
+    }
+}
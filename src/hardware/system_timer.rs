@@ -4,24 +4,52 @@ use crate::hal::{
     prelude::*,
     timer::{CountDownTimer, Event, Timer},
 };
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 use cortex_m::interrupt::Mutex;
 
-pub type CounterType = u16;
+/// Milliseconds since start, 64-bit so it never wraps in practice
+pub type CounterType = u64;
+type TickType = u32;
 
 use crate::utils::atomic_types::HasAtomic;
-type TimeType = <CounterType as HasAtomic>::Atomic;
+type AtomicTick = <TickType as HasAtomic>::Atomic;
 
-static COUNTER_MS: TimeType = TimeType::new(0);
+static COUNTER_MS: AtomicTick = AtomicTick::new(0);
+//high word of counter, extended on every 32-bit overflow (~49 days)
+static OVERFLOWS: AtomicTick = AtomicTick::new(0);
 static _TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+const MAX_HOOKS: usize = 4;
+//called every millisecond from interrupt, for outputs which must work while main loop is blocked
+type TickHooks = [Option<fn()>; MAX_HOOKS];
+static TICK_HOOKS: Mutex<Cell<TickHooks>> = Mutex::new(Cell::new([None; MAX_HOOKS]));
 pub struct SystemTimer {}
 #[allow(dead_code)]
 impl SystemTimer {
     pub fn now() -> CounterType {
-        COUNTER_MS.load(Ordering::Relaxed)
+        loop {
+            let high = OVERFLOWS.load(Ordering::Acquire);
+            let low = COUNTER_MS.load(Ordering::Acquire);
+            //overflow happened between reads, try again
+            if high == OVERFLOWS.load(Ordering::Acquire) {
+                return (high as CounterType) << 32 | low as CounterType;
+            }
+        }
+    }
+    pub fn inc(cnt: TickType) {
+        let prev = COUNTER_MS.fetch_add(cnt, Ordering::Release);
+        if prev.checked_add(cnt).is_none() {
+            OVERFLOWS.fetch_add(1, Ordering::Release);
+        }
     }
-    pub fn inc(cnt: CounterType) {
-        COUNTER_MS.fetch_add(cnt, Ordering::Relaxed);
+    pub fn add_tick_hook(hook: fn()) {
+        cortex_m::interrupt::free(|cs| {
+            let cell = TICK_HOOKS.borrow(cs);
+            let mut hooks = cell.get();
+            if let Some(slot) = hooks.iter_mut().find(|h| h.is_none()) {
+                *slot = Some(hook);
+            }
+            cell.set(hooks);
+        });
     }
     pub fn init(tim: TIM2, clocks: &hal::rcc::Clocks, mut apb1: &mut hal::rcc::APB1) {
         let mut timer = Timer::tim2(tim, &clocks, &mut apb1).start_count_down(1.khz());
@@ -41,5 +69,9 @@ fn TIM2() {
         cortex_m::interrupt::free(|cs| _TIM.borrow(cs).replace(None).unwrap())
     });
     SystemTimer::inc(1);
+    let hooks = cortex_m::interrupt::free(|cs| TICK_HOOKS.borrow(cs).get());
+    for hook in hooks.iter().flatten() {
+        hook();
+    }
     tim.clear_update_interrupt_flag();
 }
@@ -0,0 +1,94 @@
+use crate::config::CONFIG;
+use crate::hal::{pac::IWDG, time::MilliSeconds as HalMilliSeconds, watchdog::IndependentWatchdog};
+use crate::timer::{CounterTypeExt, Instant, MilliSeconds, Timer};
+use core::cell::RefCell;
+use cortex_m::interrupt::Mutex;
+use embedded_hal::watchdog::{Watchdog as _, WatchdogEnable};
+
+/// Long blocking operations, each one has its own time budget
+#[derive(Copy, Clone, PartialEq)]
+pub enum Phase {
+    GsmCheck,
+    SmsSend,
+    Delivery,
+    PowerOff,
+}
+
+impl Phase {
+    fn max_duration(&self) -> MilliSeconds {
+        match self {
+            Phase::GsmCheck => 60.sec().into(),
+            Phase::SmsSend => 120.sec().into(),
+            Phase::Delivery => (CONFIG.delivery_timeout + 10.sec()).into(),
+            Phase::PowerOff => 10.sec().into(),
+        }
+    }
+}
+
+struct Supervisor {
+    iwdg: IndependentWatchdog,
+    deadline: Option<Instant>,
+}
+
+static _WDG: Mutex<RefCell<Option<Supervisor>>> = Mutex::new(RefCell::new(None));
+
+/// Independent watchdog, fed only while program makes progress
+pub struct Watchdog {}
+#[allow(dead_code)]
+impl Watchdog {
+    const TIMEOUT: HalMilliSeconds = HalMilliSeconds(4_000);
+
+    pub fn start(iwdg: IWDG) {
+        let mut iwdg = IndependentWatchdog::new(iwdg);
+        iwdg.start(Self::TIMEOUT);
+        cortex_m::interrupt::free(|cs| {
+            *_WDG.borrow(cs).borrow_mut() = Some(Supervisor {
+                iwdg,
+                deadline: None,
+            })
+        });
+    }
+
+    ///feed from main loop, if no phase is running or phase is still in its budget
+    pub fn feed() {
+        Self::feed_if(true);
+    }
+
+    ///feed from blocking loops, only inside phase which is still in its budget.
+    ///Loop hung outside of any phase is not fed
+    pub fn feed_in_phase() {
+        Self::feed_if(false);
+    }
+
+    fn feed_if(without_phase: bool) {
+        cortex_m::interrupt::free(|cs| {
+            if let Some(wdg) = _WDG.borrow(cs).borrow_mut().as_mut() {
+                let is_alive = match wdg.deadline {
+                    Some(deadline) => Timer::now() < deadline,
+                    None => without_phase,
+                };
+                if is_alive {
+                    wdg.iwdg.feed();
+                }
+            }
+        });
+    }
+
+    pub fn begin(phase: Phase) {
+        let deadline = Timer::now().saturating_add(phase.max_duration());
+        cortex_m::interrupt::free(|cs| {
+            if let Some(wdg) = _WDG.borrow(cs).borrow_mut().as_mut() {
+                wdg.deadline = Some(deadline);
+            }
+        });
+    }
+
+    pub fn end() {
+        cortex_m::interrupt::free(|cs| {
+            if let Some(wdg) = _WDG.borrow(cs).borrow_mut().as_mut() {
+                wdg.deadline = None;
+            }
+        });
+        Watchdog::feed();
+    }
+}
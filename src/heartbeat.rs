@@ -0,0 +1,49 @@
+use crate::config::CONFIG;
+use crate::hardware::backup_registers::{BackupRegister, BackupRegisters};
+use crate::hardware::rtc_clock::RtcClock;
+use crate::timer::TimeType;
+
+/// Time of next status sms, kept as rtc counter in backup registers, so reset does not postpone it
+pub struct Heartbeat {}
+#[allow(dead_code)]
+impl Heartbeat {
+    fn period_seconds() -> Option<u32> {
+        CONFIG.heartbeat.map(|x| (x.value() / 1000) as u32)
+    }
+    fn next() -> Option<u32> {
+        let low = BackupRegisters::read(BackupRegister::HeartbeatLow) as u32;
+        let high = BackupRegisters::read(BackupRegister::HeartbeatHigh) as u32;
+        //registers are zero after backup domain power up
+        Some(low | high << 16).filter(|x| *x != 0)
+    }
+    fn set_next(value: u32) {
+        BackupRegisters::write(BackupRegister::HeartbeatLow, value as u16);
+        BackupRegisters::write(BackupRegister::HeartbeatHigh, (value >> 16) as u16);
+    }
+    ///plan first heartbeat if it was not planned before
+    pub fn init() {
+        if let (Some(_), None) = (Self::period_seconds(), Self::next()) {
+            Self::schedule_next();
+        }
+    }
+    pub fn is_due() -> bool {
+        match (Self::period_seconds(), Self::next()) {
+            (Some(_), Some(next)) => RtcClock::counter() >= next,
+            _ => false,
+        }
+    }
+    ///rtc counter was set to network time, deadline keeps its distance from now.
+    ///Otherwise first sync jumps far ahead and sends heartbeat at once
+    pub fn clock_set(before: u32, after: u32) {
+        if let Some(next) = Self::next() {
+            let left = next.saturating_sub(before);
+            Self::set_next(after.saturating_add(left).max(1));
+        }
+    }
+    ///next heartbeat one period from now, missed ones are not repeated
+    pub fn schedule_next() {
+        if let Some(period) = Self::period_seconds() {
+            Self::set_next(RtcClock::counter().saturating_add(period));
+        }
+    }
+}
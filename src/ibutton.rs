@@ -0,0 +1,62 @@
+use crate::config::CONFIG;
+use crate::hardware::one_wire::OneWire;
+use crate::timer::{MilliSeconds, Timer};
+use core::fmt;
+
+/// Serial number of touch memory key, without family code and crc
+#[derive(Copy, Clone, PartialEq)]
+pub struct KeyId(pub u64);
+
+impl KeyId {
+    fn from_rom(rom: u64) -> Self {
+        KeyId((rom >> 8) & 0xFFFF_FFFF_FFFF)
+    }
+    ///key is in enrolled list of config
+    pub fn is_enrolled(&self) -> bool {
+        CONFIG.ibutton_keys.contains(&self.0)
+    }
+}
+
+impl fmt::Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:012X}", self.0)
+    }
+}
+
+/// iButton reader, key is reported once until it is taken away
+pub struct IButton {
+    bus: OneWire,
+    timer: Timer,
+    is_touched: bool,
+}
+
+#[allow(dead_code)]
+impl IButton {
+    const TIMEOUT: MilliSeconds = MilliSeconds(250);
+    pub fn new(bus: OneWire) -> Self {
+        IButton {
+            bus,
+            timer: Timer::new(),
+            is_touched: false,
+        }
+    }
+    pub fn poll(&mut self) -> Option<KeyId> {
+        if !self.timer.every(Self::TIMEOUT) {
+            return None;
+        }
+        match self.bus.read_rom() {
+            Some(rom) if !self.is_touched => {
+                self.is_touched = true;
+                Some(KeyId::from_rom(rom))
+            }
+            Some(_) => None,
+            //bad contact gives crc errors, key is taken away only without presence
+            None => {
+                if !self.bus.reset() {
+                    self.is_touched = false;
+                }
+                None
+            }
+        }
+    }
+}
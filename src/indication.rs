@@ -1,3 +1,5 @@
+use crate::buzzer::Buzzer;
+use crate::config::CONFIG;
 use crate::hal::gpio::{Output, PushPull, Pxx};
 use crate::timer::{CounterTypeExt, Timer};
 use embedded_hal::digital::v2::OutputPin;
@@ -8,32 +10,127 @@ struct Led {
 
 impl Led {
     fn new(pin: LedPin, is_on: bool) -> Self {
-        let mut res = Led { pin, is_on };
-        if is_on {
-            res.pin.set_high().unwrap();
-        } else {
-            res.pin.set_low().unwrap();
-        }
+        let mut res = Led { pin, is_on: !is_on };
+        res.set(is_on);
         res
     }
-    fn toggle(&mut self) {
-        if self.is_on {
-            self.pin.set_low().unwrap();
-        } else {
+    fn set(&mut self, is_on: bool) {
+        if self.is_on == is_on {
+            return;
+        }
+        if is_on {
             self.pin.set_high().unwrap();
+        } else {
+            self.pin.set_low().unwrap();
         }
-        self.is_on = !self.is_on;
+        self.is_on = is_on;
     }
-    fn set_high(&mut self) {
-        self.pin.set_high().unwrap();
-        self.is_on = true;
+}
+type LedPin = Pxx<Output<PushPull>>;
+
+pub const OFF: u8 = 0;
+pub const RED: u8 = 0b01;
+pub const GREEN: u8 = 0b10;
+pub const BOTH: u8 = RED | GREEN;
+
+/// Leds lit for ms milliseconds
+#[derive(Copy, Clone)]
+pub struct Step {
+    pub leds: u8,
+    pub ms: u16,
+}
+
+pub const fn step(leds: u8, ms: u16) -> Step {
+    Step { leds, ms }
+}
+
+/// Steps played repeat times (0 - forever), overlay with higher or equal priority replaces current one
+#[derive(Copy, Clone)]
+pub struct Pattern {
+    pub steps: &'static [Step],
+    pub repeat: u8,
+    pub priority: u8,
+}
+
+const fn base(steps: &'static [Step]) -> Pattern {
+    Pattern {
+        steps,
+        repeat: 0,
+        priority: 0,
     }
-    fn set_low(&mut self) {
-        self.pin.set_low().unwrap();
-        self.is_on = false;
+}
+
+static PATTERN_NOTHING: Pattern = base(&[step(OFF, 1000)]);
+static PATTERN_IDLE: Pattern = base(&[step(GREEN, 1000)]);
+static PATTERN_IDLE_DOOR_CLOSED: Pattern = base(&[step(OFF, 500), step(GREEN, 500)]);
+static PATTERN_ERROR: Pattern = base(&[step(OFF, 1000), step(BOTH, 1000)]);
+static PATTERN_CHECK_BEFORE_ARM: Pattern = base(&[step(GREEN, 250), step(BOTH, 250)]);
+static PATTERN_READY_TO_ARM: Pattern = base(&[step(GREEN, 1000), step(BOTH, 1000)]);
+static PATTERN_EXIT_DELAY: Pattern = base(&[step(BOTH, 500), step(GREEN, 500)]);
+static PATTERN_ARMED: Pattern = base(&[step(BOTH, 1000)]);
+static PATTERN_ENTRY_DELAY: Pattern = base(&[step(RED, 250), step(GREEN, 250)]);
+static PATTERN_TAMPER: Pattern = base(&[step(RED, 250), step(OFF, 250)]);
+
+//code n is the last n flashes and the pause
+static CODE_STEPS: [Step; 15] = [
+    step(RED, 300),
+    step(OFF, 300),
+    step(RED, 300),
+    step(OFF, 300),
+    step(RED, 300),
+    step(OFF, 300),
+    step(RED, 300),
+    step(OFF, 300),
+    step(RED, 300),
+    step(OFF, 300),
+    step(RED, 300),
+    step(OFF, 300),
+    step(RED, 300),
+    step(OFF, 300),
+    step(OFF, 1500),
+];
+static LEVEL_STEPS: [Step; 11] = [
+    step(GREEN, 300),
+    step(OFF, 300),
+    step(GREEN, 300),
+    step(OFF, 300),
+    step(GREEN, 300),
+    step(OFF, 300),
+    step(GREEN, 300),
+    step(OFF, 300),
+    step(GREEN, 300),
+    step(OFF, 300),
+    step(OFF, 1500),
+];
+
+///count flashes and pause from steps of flash pairs ending with pause
+fn count_pattern(steps: &'static [Step], count: u8, repeat: u8, priority: u8) -> Pattern {
+    let max = (steps.len() - 1) / 2;
+    let count = (count as usize).min(max);
+    Pattern {
+        steps: &steps[(max - count) * 2..],
+        repeat,
+        priority,
     }
 }
-type LedPin = Pxx<Output<PushPull>>;
+
+pub static OVERLAY_SMS_SENT: Pattern = Pattern {
+    steps: &[step(OFF, 150), step(GREEN, 150)],
+    repeat: 3,
+    priority: 1,
+};
+
+pub static OVERLAY_WARNING: Pattern = Pattern {
+    steps: &[step(RED, 200), step(GREEN, 200)],
+    repeat: 10,
+    priority: 2,
+};
+
+pub static OVERLAY_WRONG_CODE: Pattern = Pattern {
+    steps: &[step(RED, 100), step(OFF, 100)],
+    repeat: 5,
+    priority: 2,
+};
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum IndicationState {
@@ -43,83 +140,150 @@ pub enum IndicationState {
     Error,
     CheckBeforeArm,
     ReadyToArm,
+    ExitDelay,
     Armed,
+    EntryDelay,
+    Tamper,
+}
+
+impl IndicationState {
+    fn pattern(self, error_code: u8) -> Pattern {
+        if self == IndicationState::Error && error_code != 0 {
+            return count_pattern(&CODE_STEPS, error_code, 0, 0);
+        }
+        *match self {
+            IndicationState::Nothing => &PATTERN_NOTHING,
+            IndicationState::Idle => &PATTERN_IDLE,
+            IndicationState::IdleDoorClosed => &PATTERN_IDLE_DOOR_CLOSED,
+            IndicationState::Error => &PATTERN_ERROR,
+            IndicationState::CheckBeforeArm => &PATTERN_CHECK_BEFORE_ARM,
+            IndicationState::ReadyToArm => &PATTERN_READY_TO_ARM,
+            IndicationState::ExitDelay => &PATTERN_EXIT_DELAY,
+            IndicationState::Armed => &PATTERN_ARMED,
+            IndicationState::EntryDelay => &PATTERN_ENTRY_DELAY,
+            IndicationState::Tamper => &PATTERN_TAMPER,
+        }
+    }
 }
+
+struct Player {
+    pattern: Pattern,
+    index: usize,
+    played: u8,
+    timer: Timer,
+}
+
+impl Player {
+    fn new(pattern: Pattern) -> Self {
+        Player {
+            pattern,
+            index: 0,
+            played: 0,
+            timer: Timer::new(),
+        }
+    }
+    ///leds of current step, None when pattern is over
+    fn poll(&mut self) -> Option<u8> {
+        let steps = self.pattern.steps;
+        if steps.is_empty() {
+            return None;
+        }
+        if self.timer.every((steps[self.index].ms as u64).mil()) {
+            self.index += 1;
+            if self.index == steps.len() {
+                self.index = 0;
+                self.played = self.played.saturating_add(1);
+            }
+        }
+        if self.pattern.repeat != 0 && self.played >= self.pattern.repeat {
+            return None;
+        }
+        Some(steps[self.index].leds)
+    }
+}
+
 pub struct Indication {
     led_red: Led,
     led_green: Led,
     state: IndicationState,
-    timer: Timer,
+    error_code: u8,
+    base: Player,
+    overlay: Option<Player>,
 }
 
+#[allow(dead_code)]
 impl Indication {
     pub fn new(pin_red: LedPin, pin_green: LedPin) -> Self {
         Indication {
             led_red: Led::new(pin_red, false),
             led_green: Led::new(pin_green, false),
             state: IndicationState::Nothing,
-            timer: Timer::new(),
+            error_code: 0,
+            base: Player::new(IndicationState::Nothing.pattern(0)),
+            overlay: None,
         }
     }
     pub fn set_state(&mut self, state: IndicationState) {
-        //initial state
         if self.state != state {
-            match state {
-                IndicationState::Nothing => {
-                    self.led_red.set_low();
-                    self.led_green.set_low();
-                }
-                IndicationState::Idle => {
-                    self.led_red.set_low();
-                    self.led_green.set_high();
-                }
-                IndicationState::IdleDoorClosed => {
-                    self.led_red.set_low();
-                    self.led_green.set_low();
-                }
-                IndicationState::Error => {
-                    self.led_red.set_low();
-                    self.led_green.set_low();
-                }
-                IndicationState::ReadyToArm | IndicationState::CheckBeforeArm => {
-                    self.led_red.set_low();
-                    self.led_green.set_high();
-                }
-                IndicationState::Armed => {
-                    self.led_red.set_high();
-                    self.led_green.set_high();
-                }
+            if CONFIG.buzzer {
+                Buzzer::on_transition(self.state, state);
             }
+            self.base = Player::new(state.pattern(self.error_code));
+            self.state = state;
+            self.poll();
         }
-        self.state = state;
     }
 
-    pub fn poll(&mut self) {
-        match self.state {
-            IndicationState::Nothing => {}
-            IndicationState::Idle => {}
-            IndicationState::IdleDoorClosed => {
-                if self.timer.every(500.mil()) {
-                    self.led_green.toggle();
-                }
-            }
-            IndicationState::Error => {
-                if self.timer.every(1.sec()) {
-                    self.led_red.toggle();
-                    self.led_green.toggle();
-                }
-            }
-            IndicationState::CheckBeforeArm => {
-                if self.timer.every(250.mil()) {
-                    self.led_red.toggle();
-                }
+    ///temporary pattern over the state one, ignored if more important overlay is shown
+    pub fn show(&mut self, pattern: &'static Pattern) {
+        self.show_pattern(*pattern);
+    }
+
+    fn show_pattern(&mut self, pattern: Pattern) {
+        if let Some(current) = &self.overlay {
+            if current.pattern.priority > pattern.priority {
+                return;
             }
-            IndicationState::ReadyToArm => {
-                if self.timer.every(1.sec()) {
-                    self.led_red.toggle();
-                }
+        }
+        self.overlay = Some(Player::new(pattern));
+        self.poll();
+    }
+
+    ///blink code shown in error state, 0 - common error blinking
+    pub fn set_error_code(&mut self, code: u8) {
+        if self.error_code == code {
+            return;
+        }
+        self.error_code = code;
+        if self.state == IndicationState::Error {
+            self.base = Player::new(self.state.pattern(code));
+        }
+    }
+
+    ///error code blinked twice over any state
+    pub fn show_error_code(&mut self, code: u8) {
+        self.show_pattern(count_pattern(&CODE_STEPS, code, 2, 2));
+    }
+
+    ///level 1..5 blinked twice with green
+    pub fn show_level(&mut self, level: u8) {
+        self.show_pattern(count_pattern(&LEVEL_STEPS, level, 2, 1));
+    }
+
+    pub fn has_overlay(&self) -> bool {
+        self.overlay.is_some()
+    }
+
+    pub fn poll(&mut self) {
+        //base keeps running under overlay, so its phase is not lost
+        let mut leds = self.base.poll().unwrap_or(OFF);
+        if let Some(overlay) = self.overlay.as_mut() {
+            match overlay.poll() {
+                Some(x) => leds = x,
+                None => self.overlay = None,
             }
-            IndicationState::Armed => {}
         }
+        self.led_red.set(leds & RED != 0);
+        self.led_green.set(leds & GREEN != 0);
     }
 }
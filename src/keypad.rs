@@ -0,0 +1,183 @@
+use crate::config::CONFIG;
+use crate::hal::gpio::{Input, OpenDrain, Output, PullUp, Pxx};
+use crate::timer::{MilliSeconds, Seconds, Timer};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use heapless::consts::*;
+use heapless::{String, Vec};
+
+static KEYS: [[char; 4]; 4] = [
+    ['1', '2', '3', 'A'],
+    ['4', '5', '6', 'B'],
+    ['7', '8', '9', 'C'],
+    ['*', '0', '#', 'D'],
+];
+
+type RowPin = Pxx<Output<OpenDrain>>;
+type ColumnPin = Pxx<Input<PullUp>>;
+
+/// Matrix keypad 4x3 or 4x4, rows are pulled low one by one and columns are read.
+/// Between scans all rows are low, so any key makes exti edge on its column
+pub struct Keypad {
+    rows: [RowPin; 4],
+    columns: Vec<ColumnPin, U4>,
+    timer: Timer,
+    last: Option<char>,
+    counter: u8,
+    is_reported: bool,
+}
+
+#[allow(dead_code)]
+impl Keypad {
+    const MAX_COUNT: u8 = 3;
+    const TIMEOUT: MilliSeconds = MilliSeconds(10);
+    pub fn new(mut rows: [RowPin; 4], columns: Vec<ColumnPin, U4>) -> Self {
+        for row in rows.iter_mut() {
+            row.set_low().unwrap();
+        }
+        Keypad {
+            rows,
+            columns,
+            timer: Timer::new(),
+            last: None,
+            counter: 0,
+            is_reported: false,
+        }
+    }
+    fn scan(&mut self) -> Option<char> {
+        let mut res = None;
+        for row in self.rows.iter_mut() {
+            row.set_high().unwrap();
+        }
+        for (r, row) in self.rows.iter_mut().enumerate() {
+            row.set_low().unwrap();
+            //let open drain line settle
+            cortex_m::asm::delay(20);
+            for (c, column) in self.columns.iter().enumerate() {
+                if let Ok(true) = column.is_low() {
+                    res = Some(KEYS[r][c]);
+                }
+            }
+            row.set_high().unwrap();
+        }
+        for row in self.rows.iter_mut() {
+            row.set_low().unwrap();
+        }
+        res
+    }
+    ///nothing is pressed
+    pub fn is_idle(&self) -> bool {
+        self.last.is_none()
+    }
+    ///debounced key, reported once per press
+    pub fn poll(&mut self) -> Option<char> {
+        if !self.timer.every(Self::TIMEOUT) {
+            return None;
+        }
+        let key = self.scan();
+        if key != self.last {
+            self.last = key;
+            self.counter = 0;
+            self.is_reported = false;
+            return None;
+        }
+        if self.counter < Self::MAX_COUNT {
+            self.counter += 1;
+            return None;
+        }
+        if key.is_some() && !self.is_reported {
+            self.is_reported = true;
+            return key;
+        }
+        None
+    }
+}
+
+/// Result of code ended with '#'
+#[derive(Copy, Clone, PartialEq)]
+pub enum PinResult {
+    User(u8),
+    Duress,
+    Wrong,
+    Locked,
+}
+
+/// Collects digits until '#', '*' clears input. Too many wrong codes lock keypad out
+pub struct PinEntry {
+    digits: String<U8>,
+    is_overflow: bool,
+    timer: Timer,
+    wrong: u8,
+    lockout: Option<Timer>,
+}
+
+#[allow(dead_code)]
+impl PinEntry {
+    const TIMEOUT: Seconds = Seconds(10);
+    pub fn new() -> Self {
+        PinEntry {
+            digits: String::new(),
+            is_overflow: false,
+            timer: Timer::new(),
+            wrong: 0,
+            lockout: None,
+        }
+    }
+    pub fn is_locked(&self) -> bool {
+        self.lockout.is_some()
+    }
+    pub fn poll(&mut self, key: Option<char>) -> Option<PinResult> {
+        if let Some(timer) = self.lockout.as_mut() {
+            if timer.waiting(&CONFIG.keypad_lockout) {
+                return None;
+            }
+            self.lockout = None;
+            self.wrong = 0;
+        }
+        let key = match key {
+            Some(x) => x,
+            None => {
+                //forget half entered code
+                if !self.digits.is_empty() && !self.timer.waiting(&Self::TIMEOUT) {
+                    self.clear();
+                }
+                return None;
+            }
+        };
+        self.timer.reset();
+        match key {
+            '*' => self.clear(),
+            '#' => {
+                let res = self.check();
+                self.clear();
+                return Some(res);
+            }
+            //digit is stored by the guard, too long code is never accepted
+            '0'..='9' if self.digits.push(key).is_err() => self.is_overflow = true,
+            _ => {}
+        }
+        None
+    }
+    fn clear(&mut self) {
+        self.digits.clear();
+        self.is_overflow = false;
+    }
+    fn check(&mut self) -> PinResult {
+        if !self.is_overflow && !self.digits.is_empty() {
+            if CONFIG.duress_code == Some(self.digits.as_str()) {
+                self.wrong = 0;
+                return PinResult::Duress;
+            }
+            let code = self.digits.as_str();
+            if let Some(user) = CONFIG.user_codes.iter().position(|x| *x == code) {
+                self.wrong = 0;
+                return PinResult::User(user as u8);
+            }
+        }
+        self.wrong += 1;
+        if self.wrong >= CONFIG.keypad_attempts {
+            self.lockout = Some(Timer::new());
+            return PinResult::Locked;
+        }
+        PinResult::Wrong
+    }
+}
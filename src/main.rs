@@ -6,7 +6,7 @@
 use cortex_m_rt::entry;
 extern crate hal;
 use crate::hal::{
-    gpio::{gpiob, Output, PushPull},
+    gpio::{gpiob, Edge, ExtiPin, Output, PushPull},
     pac::Peripherals,
     prelude::*,
 };
@@ -20,25 +20,61 @@ type Sim900PowerPin = gpiob::PB5<Output<PushPull>>;
 mod utils;
 
 mod sim900;
+mod siren;
+mod sms_template;
+use siren::Siren;
 use sim900::Sim900;
 
 mod button;
+mod byte_stream;
+mod buzzer;
+use buzzer::Buzzer;
+mod crash_record;
+mod event_log;
+mod config;
+use config::CONFIG;
+mod clock;
+use clock::Clock;
 use button::Button;
+mod auto_arm;
+mod aux_sensors;
+use aux_sensors::AuxSensors;
 mod door_sensor;
-use door_sensor::DoorSensor;
+mod motion_sensor;
+use motion_sensor::MotionSensor;
+mod zone;
+use door_sensor::{DoorInput, DoorSensor};
 mod hardware;
+mod heartbeat;
+use hardware::backup_registers::BackupRegisters;
+use hardware::low_power::LowPower;
+use hardware::one_wire::OneWire;
+use hardware::reset_cause;
+use hardware::watchdog::Watchdog;
 mod indication;
+mod ibutton;
+use ibutton::IButton;
+mod keypad;
+use keypad::Keypad;
+mod pdu;
+mod power_monitor;
+use hardware::analog::Analog;
+use power_monitor::PowerMonitor;
+mod scheduler;
+mod thermometer;
+use thermometer::Thermometer;
 mod timer;
-use timer::Timer;
+use timer::{CounterTypeExt, Timer};
 mod usart;
 use indication::Indication;
 mod algorithm;
-use algorithm::MainLogic;
+use algorithm::{MainLogic, Sensors};
 
 #[entry]
 fn main() -> ! {
-    //let cp = cortex_m::Peripherals::take().unwrap();
+    let cp = cortex_m::Peripherals::take().unwrap();
     let dp = Peripherals::take().unwrap();
+    let reset_cause = reset_cause::read_and_clear();
 
     let mut flash = dp.FLASH.constrain();
     let mut rcc = dp.RCC.constrain();
@@ -46,17 +82,71 @@ fn main() -> ! {
     let mut gpiob = dp.GPIOB.split(&mut rcc.apb2);
     let mut gpioa = dp.GPIOA.split(&mut rcc.apb2);
     let power_pin: Sim900PowerPin = gpiob.pb5.into_push_pull_output(&mut gpiob.crl);
-    let button_power = gpiob.pb6.into_pull_up_input(&mut gpiob.crl).downgrade();
-    let button_door = gpiob.pb12.into_pull_up_input(&mut gpiob.crh).downgrade();
+    let mut button_power = gpiob.pb6.into_pull_up_input(&mut gpiob.crl).downgrade();
+    let mut button_door = gpiob.pb12.into_pull_up_input(&mut gpiob.crh).downgrade();
+    let mut tamper = gpiob.pb13.into_pull_up_input(&mut gpiob.crh).downgrade();
+    let mut pir = gpiob.pb1.into_pull_up_input(&mut gpiob.crl).downgrade();
+    let flood = gpiob.pb15.into_pull_up_input(&mut gpiob.crh).downgrade();
+    let smoke = gpiob.pb7.into_pull_up_input(&mut gpiob.crl).downgrade();
+    let thermometer_pin = gpioa.pa7.into_open_drain_output(&mut gpioa.crl).downgrade();
     let led_red = gpioa.pa11.into_push_pull_output(&mut gpioa.crh).downgrade();
     let led_green = gpioa.pa12.into_push_pull_output(&mut gpioa.crh).downgrade();
+    let supply_pin = gpioa.pa0.into_analog(&mut gpioa.crl);
+    let battery_pin = gpioa.pa1.into_analog(&mut gpioa.crl);
+    let door_loop = gpiob.pb0.into_analog(&mut gpiob.crl);
+    let siren_pin = gpiob.pb14.into_push_pull_output(&mut gpiob.crh).downgrade();
+    let buzzer_pin = gpioa.pa6.into_alternate_push_pull(&mut gpioa.crl);
+    let keypad_rows = [
+        gpioa.pa2.into_open_drain_output(&mut gpioa.crl).downgrade(),
+        gpioa.pa3.into_open_drain_output(&mut gpioa.crl).downgrade(),
+        gpioa.pa4.into_open_drain_output(&mut gpioa.crl).downgrade(),
+        gpioa.pa5.into_open_drain_output(&mut gpioa.crl).downgrade(),
+    ];
+    let ibutton_pin = gpioa.pa8.into_open_drain_output(&mut gpioa.crh).downgrade();
+    let mut keypad_columns = [
+        gpiob.pb8.into_pull_up_input(&mut gpiob.crh).downgrade(),
+        gpiob.pb9.into_pull_up_input(&mut gpiob.crh).downgrade(),
+        gpiob.pb10.into_pull_up_input(&mut gpiob.crh).downgrade(),
+        gpiob.pb11.into_pull_up_input(&mut gpiob.crh).downgrade(),
+    ];
+    Analog::init(dp.ADC1, &mut rcc.apb2, clocks);
     //_LED.set(led);
     Timer::init_system(dp.TIM2, &clocks, &mut rcc.apb1);
+    let mut pwr = dp.PWR;
+    let backup_domain = rcc.bkp.constrain(dp.BKP, &mut rcc.apb1, &mut pwr);
+    BackupRegisters::init(backup_domain);
+    Clock::init_system(dp.RTC);
+    let mut low_power = LowPower::new(cp.SCB, pwr, dp.EXTI);
     //let mut itm = cp.ITM;
     //iprintln!(&mut itm.stim[0], "hello wordl!");
 
     // USART1
     let mut afio = dp.AFIO.constrain(&mut rcc.apb2);
+    Buzzer::init(
+        hal::timer::Timer::tim3(dp.TIM3, &clocks, &mut rcc.apb1).pwm(
+            buzzer_pin,
+            &mut afio.mapr,
+            1.khz(),
+        ),
+    );
+    //button and door wake up from stop mode
+    for pin in [&mut button_power, &mut button_door, &mut tamper].iter_mut() {
+        pin.make_interrupt_source(&mut afio);
+        pin.trigger_on_edge(low_power.exti(), Edge::RISING_FALLING);
+        pin.enable_interrupt(low_power.exti());
+    }
+    if CONFIG.pir {
+        pir.make_interrupt_source(&mut afio);
+        pir.trigger_on_edge(low_power.exti(), Edge::RISING_FALLING);
+        pir.enable_interrupt(low_power.exti());
+    }
+    //pressed key pulls its column to low row
+    let keypad_columns_used = CONFIG.keypad_columns.min(4) as usize;
+    for pin in keypad_columns[..keypad_columns_used].iter_mut() {
+        pin.make_interrupt_source(&mut afio);
+        pin.trigger_on_edge(low_power.exti(), Edge::FALLING);
+        pin.enable_interrupt(low_power.exti());
+    }
     let adapter = usart::create_adapter(
         dp.USART1,
         &mut afio.mapr,
@@ -70,28 +160,77 @@ fn main() -> ! {
     usart::_USART.set(adapter);
 
     let sim900 = Sim900::new(power_pin.downgrade());
+    Siren::init(siren_pin);
+    let (door_input, door_latch_line) = if CONFIG.door_eol {
+        (DoorInput::EndOfLine(door_loop), None)
+    } else {
+        (DoorInput::Digital(button_door), Some(12)) //PB12
+    };
 
     let indication: Indication = Indication::new(led_red, led_green);
+    let keypad = match keypad_columns_used {
+        0 => None,
+        used => Some(Keypad::new(
+            keypad_rows,
+            IntoIterator::into_iter(keypad_columns).take(used).collect(),
+        )),
+    };
+    let ibutton = match CONFIG.ibutton {
+        true => Some(IButton::new(OneWire::new(ibutton_pin, clocks.sysclk().0))),
+        false => None,
+    };
+    let motion_sensor = match CONFIG.pir {
+        true => Some(MotionSensor::new(DoorSensor::new(
+            DoorInput::Digital(pir),
+            &CONFIG.pir_sampling,
+            Some(1), //PB1
+        ))),
+        false => None,
+    };
+    let aux_sensors = AuxSensors::new(
+        match CONFIG.thermometer {
+            true => Some(Thermometer::new(OneWire::new(thermometer_pin, clocks.sysclk().0))),
+            false => None,
+        },
+        match CONFIG.flood {
+            true => Some(DoorSensor::new(DoorInput::Digital(flood), &CONFIG.flood_sampling, None)),
+            false => None,
+        },
+        match CONFIG.smoke {
+            true => Some(DoorSensor::new(DoorInput::Digital(smoke), &CONFIG.smoke_sampling, None)),
+            false => None,
+        },
+    );
     let mut algorithm = MainLogic::new(
         sim900,
         indication,
         Button::new(button_power, true),
-        DoorSensor::new(button_door),
+        keypad,
+        ibutton,
+        Sensors {
+            door: DoorSensor::new(door_input, &CONFIG.door_sampling, door_latch_line),
+            tamper: DoorSensor::new(DoorInput::Digital(tamper), &CONFIG.tamper_sampling, None),
+            motion: motion_sensor,
+            aux: aux_sensors,
+            power_monitor: PowerMonitor::new(supply_pin, battery_pin),
+        },
     );
-    algorithm.init();
+    Watchdog::start(dp.IWDG);
+    algorithm.init(reset_cause);
     loop {
         algorithm.poll();
+        if CONFIG.low_power && algorithm.can_stop() {
+            low_power.stop(1.sec());
+        } else {
+            low_power.sleep();
+        }
     }
 }
 use core::panic::PanicInfo;
-use core::sync::atomic::{self, Ordering};
 #[inline(never)]
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    if let Some(s) = _info.payload().downcast_ref::<&str>() {
-        usart::_USART.get().write_data(s.as_bytes());
-    }
-    loop {
-        atomic::compiler_fence(Ordering::SeqCst);
-    }
+fn panic(info: &PanicInfo) -> ! {
+    //record is reported after restart
+    crash_record::save_panic(info);
+    cortex_m::peripheral::SCB::sys_reset();
 }
@@ -0,0 +1,65 @@
+use crate::config::CONFIG;
+use crate::door_sensor::{DoorSensor, DoorState};
+use crate::timer::Timer;
+
+/// PIR detector with relay output. It trips after several pulses within window
+/// started by the first one, single pulses from heat or light flashes are ignored
+pub struct MotionSensor {
+    contact: DoorSensor,
+    pulses: u8,
+    window: Option<Timer>,
+    warm_up: Timer,
+    is_warm: bool,
+}
+
+#[allow(dead_code)]
+impl MotionSensor {
+    pub fn new(contact: DoorSensor) -> Self {
+        MotionSensor {
+            contact,
+            pulses: 0,
+            window: None,
+            warm_up: Timer::new(),
+            is_warm: false,
+        }
+    }
+    ///no change in progress and no pulses are counted
+    pub fn is_settled(&self) -> bool {
+        self.contact.is_settled() && self.window.is_none()
+    }
+    pub fn set_latching(&mut self, is_latching: bool) {
+        self.contact.set_latching(is_latching);
+        self.window = None;
+    }
+    ///true when motion is detected
+    pub fn poll(&mut self) -> bool {
+        let state = self.contact.state();
+        //detector output is not stable after power on
+        if !self.is_warm {
+            if self.warm_up.waiting(&CONFIG.pir_warm_up) {
+                return false;
+            }
+            self.is_warm = true;
+        }
+        if let Some(window) = self.window.as_mut() {
+            if !window.waiting(&CONFIG.pir_window) {
+                self.window = None;
+            }
+        }
+        if state != Some(DoorState::Opened) {
+            return false;
+        }
+        match self.window {
+            Some(_) => self.pulses += 1,
+            None => {
+                self.window = Some(Timer::new());
+                self.pulses = 1;
+            }
+        }
+        if self.pulses < CONFIG.pir_pulses {
+            return false;
+        }
+        self.window = None;
+        true
+    }
+}
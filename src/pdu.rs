@@ -0,0 +1,100 @@
+use core::fmt::Write;
+use heapless::consts::*;
+use heapless::String;
+
+/// hex PDU of SMS-SUBMIT: max 70 UCS2 chars plus header
+pub type Pdu = String<U320>;
+const MAX_UNITS: usize = 70;
+//sms-submit with TP-SRR, status report requested
+const SUBMIT: u8 = 0x01;
+const STATUS_REPORT_REQUEST: u8 = 0x20;
+const STATUS_REPORT: u8 = 0x02;
+
+/// Fate of sent sms from TP-Status of report
+#[derive(Copy, Clone, PartialEq)]
+pub enum DeliveryStatus {
+    Delivered,
+    Pending,
+    Failed,
+}
+
+/// Report of service centre about sms with message reference
+#[derive(Copy, Clone)]
+pub struct StatusReport {
+    pub reference: u8,
+    pub status: DeliveryStatus,
+}
+
+fn push_semi_octets(pdu: &mut Pdu, phone: &str) -> Result<(), ()> {
+    for pair in phone.as_bytes().chunks(2) {
+        //digits are swapped in every octet, odd count padded with F
+        let low = pair[0] as char;
+        let high = if pair.len() == 2 { pair[1] as char } else { 'F' };
+        pdu.push(high)?;
+        pdu.push(low)?;
+    }
+    Ok(())
+}
+
+///count of utf16 units and chars of text which fit in one sms
+fn fit_text(text: &str) -> (usize, usize) {
+    let mut units = 0;
+    let mut chars = 0;
+    for c in text.chars() {
+        if units + c.len_utf16() > MAX_UNITS {
+            break;
+        }
+        units += c.len_utf16();
+        chars += 1;
+    }
+    (units, chars)
+}
+
+/// encode text to PDU with UCS2 coding, phone in international format.
+/// Text is cut to one SMS
+pub fn encode_sms(phone: &str, text: &str, status_report: bool) -> Result<Pdu, ()> {
+    let phone = phone.trim_start_matches('+');
+    let mut pdu = Pdu::new();
+    let first_octet = match status_report {
+        true => SUBMIT | STATUS_REPORT_REQUEST,
+        false => SUBMIT,
+    };
+    //default smsc, message reference by modem
+    write!(pdu, "00{:02X}00", first_octet).map_err(|_| ())?;
+    write!(pdu, "{:02X}91", phone.len()).map_err(|_| ())?;
+    push_semi_octets(&mut pdu, phone)?;
+    //protocol id, ucs2 data coding
+    pdu.push_str("0008")?;
+    let (units, chars) = fit_text(text);
+    write!(pdu, "{:02X}", units * 2).map_err(|_| ())?;
+    for c in text.chars().take(chars) {
+        let mut buf = [0_u16; 2];
+        for unit in c.encode_utf16(&mut buf).iter() {
+            write!(pdu, "{:04X}", unit).map_err(|_| ())?;
+        }
+    }
+    Ok(pdu)
+}
+
+/// decode hex PDU of SMS-STATUS-REPORT from +CDS
+pub fn decode_status_report(pdu: &str) -> Option<StatusReport> {
+    let octet =
+        |i: usize| -> Option<u8> { u8::from_str_radix(pdu.get(i * 2..i * 2 + 2)?, 16).ok() };
+    //service centre address with its length
+    let mut i = octet(0)? as usize + 1;
+    if octet(i)? & 0x03 != STATUS_REPORT {
+        return None;
+    }
+    let reference = octet(i + 1)?;
+    //recipient address: digit count, type and semi octets
+    let digits = octet(i + 2)? as usize;
+    i += 4 + digits.div_ceil(2);
+    //service centre time stamp and discharge time
+    i += 14;
+    let status = match octet(i)? {
+        0x00..=0x1F => DeliveryStatus::Delivered,
+        0x20..=0x3F => DeliveryStatus::Pending,
+        _ => DeliveryStatus::Failed,
+    };
+    Some(StatusReport { reference, status })
+}
@@ -0,0 +1,112 @@
+use crate::config::CONFIG;
+use crate::hal::gpio::{
+    gpioa::{PA0, PA1},
+    Analog as AnalogMode,
+};
+use crate::hardware::analog::Analog;
+use crate::timer::{MilliSeconds, Timer};
+
+pub enum PowerEvent {
+    MainsLost,
+    MainsRestored,
+    BatteryLow,
+}
+
+/// Measures supply and backup battery voltage through dividers
+pub struct PowerMonitor {
+    supply_pin: PA0<AnalogMode>,
+    battery_pin: PA1<AnalogMode>,
+    timer: Timer,
+    is_mains_ok: bool,
+    is_battery_low: bool,
+    //samples in a row that disagree with current state
+    mains_count: u8,
+    battery_count: u8,
+    supply_mv: u16,
+    battery_mv: u16,
+}
+
+#[allow(dead_code)]
+impl PowerMonitor {
+    const TIMEOUT: MilliSeconds = MilliSeconds(1000);
+    const SAMPLES: u8 = 3;
+    ///starts as if mains and battery are good, so bad supply at power-up is reported too
+    pub fn new(supply_pin: PA0<AnalogMode>, battery_pin: PA1<AnalogMode>) -> Self {
+        let mut res = PowerMonitor {
+            supply_pin,
+            battery_pin,
+            timer: Timer::new(),
+            is_mains_ok: true,
+            is_battery_low: false,
+            mains_count: 0,
+            battery_count: 0,
+            supply_mv: 0,
+            battery_mv: 0,
+        };
+        res.measure();
+        res
+    }
+    ///true once condition holds for SAMPLES polls in a row, short dips are ignored
+    fn debounce(count: &mut u8, condition: bool) -> bool {
+        if !condition {
+            *count = 0;
+            return false;
+        }
+        *count += 1;
+        if *count < Self::SAMPLES {
+            return false;
+        }
+        *count = 0;
+        true
+    }
+    fn measure(&mut self) {
+        if let Some(mv) = Analog::millivolts(&mut self.supply_pin) {
+            self.supply_mv = CONFIG.supply_divider.input_mv(mv);
+        }
+        if let Some(mv) = Analog::millivolts(&mut self.battery_pin) {
+            self.battery_mv = CONFIG.battery_divider.input_mv(mv);
+        }
+    }
+    pub fn supply_mv(&self) -> u16 {
+        self.supply_mv
+    }
+    pub fn battery_mv(&self) -> u16 {
+        self.battery_mv
+    }
+    pub fn is_mains_ok(&self) -> bool {
+        self.is_mains_ok
+    }
+    pub fn poll(&mut self) -> Option<PowerEvent> {
+        if !self.timer.every(Self::TIMEOUT) {
+            return None;
+        }
+        self.measure();
+        let hysteresis = CONFIG.voltage_hysteresis_mv;
+        let is_mains_changed = if self.is_mains_ok {
+            self.supply_mv < CONFIG.mains_lost_mv
+        } else {
+            self.supply_mv >= CONFIG.mains_lost_mv + hysteresis
+        };
+        if Self::debounce(&mut self.mains_count, is_mains_changed) {
+            self.is_mains_ok = !self.is_mains_ok;
+            return Some(if self.is_mains_ok {
+                PowerEvent::MainsRestored
+            } else {
+                PowerEvent::MainsLost
+            });
+        }
+        //battery is discharged only while running from it
+        let is_battery_changed = if self.is_battery_low {
+            self.battery_mv >= CONFIG.battery_low_mv + hysteresis
+        } else {
+            !self.is_mains_ok && self.battery_mv < CONFIG.battery_low_mv
+        };
+        if Self::debounce(&mut self.battery_count, is_battery_changed) {
+            self.is_battery_low = !self.is_battery_low;
+            if self.is_battery_low {
+                return Some(PowerEvent::BatteryLow);
+            }
+        }
+        None
+    }
+}
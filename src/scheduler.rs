@@ -0,0 +1,99 @@
+use crate::timer::{Instant, MilliSeconds, TimeType, Timer};
+use heapless::consts::*;
+use heapless::Vec;
+
+struct Task<J: Copy> {
+    job: J,
+    name: &'static str,
+    period: MilliSeconds,
+    jitter: MilliSeconds,
+    due: Instant,  //planned time without jitter, keeps period from drifting
+    next: Instant, //due + random jitter
+}
+
+/// Cooperative scheduler of periodic background jobs, polled from main loop
+pub struct Scheduler<J: Copy> {
+    tasks: Vec<Task<J>, U8>,
+    seed: u32,
+}
+
+#[allow(dead_code)]
+impl<J: Copy + PartialEq> Scheduler<J> {
+    pub fn new(seed: u32) -> Self {
+        Scheduler {
+            tasks: Vec::new(),
+            seed: seed | 1,
+        }
+    }
+
+    /// register job, first run after one period
+    pub fn add<T: TimeType, U: TimeType>(
+        &mut self,
+        job: J,
+        name: &'static str,
+        period: T,
+        jitter: U,
+    ) -> Result<(), ()> {
+        let now = Timer::now();
+        let period = MilliSeconds(period.value());
+        let mut task = Task {
+            job,
+            name,
+            period,
+            jitter: MilliSeconds(jitter.value()),
+            due: now.saturating_add(period),
+            next: now,
+        };
+        task.next = task.due.saturating_add(self.random(task.jitter));
+        self.tasks.push(task).map_err(|_| ())
+    }
+
+    /// returns one job which deadline has come, and plans its next run
+    pub fn poll(&mut self) -> Option<J> {
+        let now = Timer::now();
+        let index = self.tasks.iter().position(|t| t.next <= now)?;
+        let jitter = self.random(self.tasks[index].jitter);
+        let task = &mut self.tasks[index];
+        task.due = task.due.saturating_add(task.period);
+        if task.due <= now {
+            //device was busy for longer than period, do not run missed ones
+            task.due = now.saturating_add(task.period);
+        }
+        task.next = task.due.saturating_add(jitter);
+        Some(task.job)
+    }
+
+    /// nearest time when some job must run
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.tasks.iter().map(|t| t.next).fold(None, |res, next| match res {
+            Some(x) if x <= next => Some(x),
+            _ => Some(next),
+        })
+    }
+
+    /// move next run of job to exact time, jitter is not applied
+    pub fn set_next(&mut self, job: J, at: Instant) {
+        if let Some(task) = self.tasks.iter_mut().find(|t| t.job == job) {
+            task.due = at;
+            task.next = at;
+        }
+    }
+
+    pub fn name(&self, job: J) -> &'static str {
+        match self.tasks.iter().find(|t| t.job == job) {
+            Some(task) => task.name,
+            None => "",
+        }
+    }
+
+    fn random(&mut self, max: MilliSeconds) -> MilliSeconds {
+        if max.0 == 0 {
+            return MilliSeconds(0);
+        }
+        //xorshift32
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        MilliSeconds(self.seed as u64 % max.0)
+    }
+}
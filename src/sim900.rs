@@ -1,10 +1,13 @@
-use core::sync::atomic::{self, Ordering};
+use core::sync::atomic::{self, AtomicBool, Ordering};
 #[allow(dead_code)]
 static SIM900_AT: &str = "AT\r\n";
 static SIM900_DATA_MODE: &str = "AT+CBST=71,0,1\r\n";
 //static SIM900_INSERT_PINCODE: &str = "AT+CPIN=\"";
 static SIM900_GET_SIM_STATUS: &str = "AT+CPIN?\r\n";
 //static SIM900_GET_MONEY: &str = "ATD#100#;\r\n";
+static SIM900_USSD: &str = "AT+CUSD=1,\"";
+static SIM900_GET_REGISTRATION: &str = "AT+CREG?\r\n";
+static SIM900_GET_SIGNAL: &str = "AT+CSQ\r\n";
 //static SIM900_GET_OPSOS: &str = "AT+COPS?\r\n";
 //static SIM900_TEXT_MODE_ON: &str = "AT+CMGF=1\r\n";
 static SIM900_PDU_MODE_ON: &str = "AT+CMGF=0\r\n";
@@ -12,30 +15,61 @@ static SIM900_PDU_MODE_ON: &str = "AT+CMGF=0\r\n";
 //static SIM900_AON_ENABLE: &str = "AT+CLIP=1\r\n";
 //static SIM900_ECHO_OFF: &str = "ATE0\r\n";
 static SIM900_END: &str = "\r\n";
-//static SIM900_RING: &str = "ATD";
+static SIM900_RING: &str = "ATD";
 //static SIM900_ATA: &str = "ATA\r\n";
 //static SIM900_LEAVE_CMD_MODE: &str = "+++";
-//static SIM900_DISCONNECT: &str = "ATH0\r\n";
-//static SIM900_GET_TIME: &str = "AT+CCLK?\r\n";
+static SIM900_DISCONNECT: &str = "ATH0\r\n";
+static SIM900_STATUS_REPORTS_ON: &str = "AT+CNMI=2,0,0,1,0\r\n"; //status reports as +CDS
+static SIM900_SINGLE_CONNECTION: &str = "AT+CIPMUX=0\r\n";
+static SIM900_DATA_HEADER_ON: &str = "AT+CIPHEAD=1\r\n"; //received data as +IPD,<len>:<data>
+static SIM900_SET_APN: &str = "AT+CSTT=\"";
+static SIM900_GPRS_UP: &str = "AT+CIICR\r\n";
+static SIM900_GET_IP: &str = "AT+CIFSR\r\n";
+static SIM900_CONNECT: &str = "AT+CIPSTART=\"";
+static SIM900_SEND_DATA: &str = "AT+CIPSEND=";
+static SIM900_CLOSE: &str = "AT+CIPCLOSE\r\n";
+static SIM900_GPRS_DOWN: &str = "AT+CIPSHUT\r\n";
+static SIM900_GET_TIME: &str = "AT+CCLK?\r\n";
+static SIM900_NETWORK_TIME_ON: &str = "AT+CLTS=1;&W\r\n"; //saved in profile, applied on next registration
 //static SIM900_SET_TIME: &str = "AT+CCLK=\"";
 static SIM900_SEND_SMS: &str = "AT+CMGS=\"";
-//static SIM900_TERMINATOR: &str = ";";
+static SIM900_TERMINATOR: &str = ";";
 static SIM900_CMD_ENTER: &str = "\x1a\r";
 //static SIM900_CMD_CANCEL: &str = "\x1b\r";
 //static ANSWER_EMPTY_LINE: &str = "\r\n";
 //static ANSWER_OPSOS: &str = "\r\n+COPS";
 //static ANSWER_MONEY: &str = "\r\n+CUSD:";
 //static ANSWER_SIM_STATUS: &str = "\r\n+CPIN:";
+static ANSWER_SIM_READY: &str = "READY";
+static ANSWER_REGISTRATION: &str = "+CREG: ";
+static ANSWER_SIGNAL: &str = "+CSQ: ";
+static ANSWER_USSD: &str = "+CUSD: ";
 //static ANSWER_INCOMING_RING: &str = "\r\nRING\r\n";
 //static ANSWER_INCOMING_PHONE_NUMBER: &str = "\r\n+CLIP:";
 //static ANSWER_ENTER_SMS: &str = ">";
 static ANSWER_OK: &str = "\r\nOK";
 static ANSWER_ERROR: &str = "\r\nERROR";
+static ANSWER_TIME: &str = "+CCLK: \"";
+static ANSWER_SMS_SENT: &str = "+CMGS: ";
+static URC_STATUS_REPORT: &str = "+CDS:";
+static ANSWER_CONNECTED: &str = "CONNECT OK";
+static ANSWER_ALREADY_CONNECTED: &str = "ALREADY CONNECT";
+static ANSWER_ENTER_DATA: &str = ">";
+static ANSWER_DATA_SENT: &str = "SEND OK";
+static ANSWER_GPRS_DOWN: &str = "SHUT OK";
+static URC_DATA: &[u8] = b"+IPD,";
+static URC_CLOSED: &[u8] = b"CLOSED";
+static URC_GPRS_LOST: &[u8] = b"+PDP: DEACT";
+static URC_UNDER_VOLTAGE: &str = "UNDER-VOLTAGE"; //WARNNING or POWER DOWN
 //static ANSWER_CONNECT: &str = "\r\nCONNECT";
 //static ANSWER_NO_DIALTONE: &str = "\r\nNO DIALTONE";
 //static ANSWER_NO_CARRIER: &str = "\r\nNO CARRIER";
 
+use crate::byte_stream::ByteStream;
+use crate::clock::DateTime;
+use crate::config::Apn;
 use crate::hal::gpio::{Output, PushPull, Pxx};
+use crate::pdu::{self, StatusReport};
 use crate::timer::{CounterTypeExt, MilliSeconds, TimeType, Timer};
 use crate::usart::_USART;
 use crate::utils::span::Span;
@@ -46,12 +80,47 @@ extern crate heapless;
 use core::fmt::Write;
 use heapless::consts::*;
 use heapless::String;
+static UNDER_VOLTAGE: AtomicBool = AtomicBool::new(false);
+
+///unsolicited codes can come together with any answer
+fn check_urc(data: &[u8]) {
+    if let Ok(s) = str::from_utf8(data) {
+        if s.find(URC_UNDER_VOLTAGE).is_some() {
+            UNDER_VOLTAGE.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+///pdu of +CDS: <length>\r\n<pdu>\r\n, None until pdu line is read completely
+fn status_report_pdu(urc: &str) -> Option<&str> {
+    let rest = urc[urc.find('\n')? + 1..].trim_start();
+    let end = rest.find('\r')?;
+    Some(&rest[..end])
+}
+
+///listen for unsolicited codes without request
+fn wait_urc<'a, T: TimeType>(timeout: T) -> Option<&'a str> {
+    _USART.get().prepare_to_read();
+    let Span(data, len) = _USART.get().read_timeout(timeout)?;
+    check_urc(&data[0..len]);
+    str::from_utf8(&data[0..len]).ok()
+}
+
 ///send data and blocking waiting result
 fn write_and_wait_answer<'a, T: TimeType>(arr: &str, timeout: T) -> Option<Span<'a>> {
+    write_bytes_and_wait_answer(arr.as_bytes(), timeout)
+}
+
+///send raw bytes and blocking waiting result
+fn write_bytes_and_wait_answer<'a, T: TimeType>(arr: &[u8], timeout: T) -> Option<Span<'a>> {
     //let mut t = Timer::new();
     _USART.get().prepare_to_read();
-    _USART.get().write_data(arr.as_bytes());
-    _USART.get().read_timeout(timeout)
+    _USART.get().write_data(arr);
+    let res = _USART.get().read_timeout(timeout);
+    if let Some(Span(data, len)) = res {
+        check_urc(&data[0..len]);
+    }
+    res
     //if let Some(r) = _USART.get().read_timeout(timeout) {
     //   return r;
     //}
@@ -102,6 +171,17 @@ fn request<'a, T: TimeType>(arr: &str, timeout: T) -> Result<(), RequestError<'a
     return result;
 }
 
+///send data and blocking waiting result, return answer text on success
+fn request_answer<'a, T: TimeType>(arr: &str, timeout: T) -> Result<&'a str, RequestError<'a>> {
+    match write_and_wait_answer(arr, timeout) {
+        None => Err(RequestError::ETimeout),
+        Some(Span(data, len)) => match str::from_utf8(&data[0..len]) {
+            Ok(s) => parse(s, len).map(|_| s),
+            Err(_) => Err(RequestError::ENoAnswer),
+        },
+    }
+}
+
 #[derive(Copy, Clone)]
 pub enum Sim900State {
     Unknown,
@@ -109,13 +189,160 @@ pub enum Sim900State {
     NoAnswer,
     BadAnswer,
     NoSim,
+    SimLocked,
+}
+
+/// Reason why gsm check or sms sending failed, shown as blink code
+#[derive(Copy, Clone, PartialEq)]
+pub enum GsmFault {
+    NoModem,
+    NoSim,
+    SimLocked,
+    NoRegistration,
+    WeakSignal,
+    LowBalance,
+    SmsRejected,
 }
+
+#[allow(dead_code)]
+impl GsmFault {
+    ///number of red flashes
+    pub fn code(self) -> u8 {
+        match self {
+            GsmFault::NoModem => 1,
+            GsmFault::NoSim => 2,
+            GsmFault::SimLocked => 3,
+            GsmFault::NoRegistration => 4,
+            GsmFault::WeakSignal => 5,
+            GsmFault::LowBalance => 6,
+            GsmFault::SmsRejected => 7,
+        }
+    }
+    ///fault of modem by its last answer, modem which answers fails with default
+    pub fn from_state(state: Sim900State, default: GsmFault) -> GsmFault {
+        match state {
+            Sim900State::Unknown | Sim900State::NoAnswer => GsmFault::NoModem,
+            Sim900State::NoSim => GsmFault::NoSim,
+            Sim900State::SimLocked => GsmFault::SimLocked,
+            Sim900State::Good | Sim900State::BadAnswer => default,
+        }
+    }
+}
+
+///amount of money in ussd text, text may be ucs2 hex encoded
+fn parse_balance(text: &str) -> Option<i32> {
+    let mut decoded: String<U160> = String::new();
+    let is_ucs2 = text.len().is_multiple_of(4) && text.bytes().all(|b| b.is_ascii_hexdigit());
+    if is_ucs2 {
+        for i in (0..text.len()).step_by(4) {
+            let code = u16::from_str_radix(&text[i..i + 4], 16).ok()?;
+            let c = core::char::from_u32(code as u32).unwrap_or(' ');
+            if decoded.push(c).is_err() {
+                break;
+            }
+        }
+    } else {
+        for c in text.chars() {
+            if decoded.push(c).is_err() {
+                break;
+            }
+        }
+    }
+    find_amount(&decoded)
+}
+
+///currency right after number, "123 р.", "123руб", "123 RUB", "123₽"
+fn is_currency(rest: &str) -> bool {
+    let rest = rest.trim_start();
+    ["р", "Р", "rub", "Rub", "RUB", "₽"]
+        .iter()
+        .any(|x| rest.starts_with(x))
+}
+
+///number followed by currency, otherwise last number with kopecks. Dates, times and
+///tariff names like "Баланс на 01.10.2024 12:00, Тариф 500" are skipped
+fn find_amount(text: &str) -> Option<i32> {
+    let bytes = text.as_bytes();
+    let is_digit = |i: usize| i < bytes.len() && bytes[i].is_ascii_digit();
+    let mut last_decimal = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        if !is_digit(i) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while is_digit(i) {
+            i += 1;
+        }
+        let value: Option<i32> = text[start..i].parse().ok();
+        let before = if start > 0 { bytes[start - 1] } else { b' ' };
+        let mut is_date = before == b'.' || before == b'/' || before == b':';
+        let mut is_decimal = false;
+        if (i < bytes.len() && (bytes[i] == b'.' || bytes[i] == b',')) && is_digit(i + 1) {
+            let fraction = i + 1;
+            i = fraction;
+            while is_digit(i) {
+                i += 1;
+            }
+            is_decimal = i - fraction == 2;
+        }
+        //rest of date or time
+        if i < bytes.len() && (bytes[i] == b'.' || bytes[i] == b'/' || bytes[i] == b':')
+            && is_digit(i + 1)
+        {
+            is_date = true;
+        }
+        let value = match (value, is_date) {
+            (Some(x), false) if before == b'-' => -x,
+            (Some(x), false) => x,
+            _ => continue,
+        };
+        if is_currency(&text[i..]) {
+            return Some(value);
+        }
+        if is_decimal {
+            last_decimal = Some(value);
+        }
+    }
+    last_decimal
+}
+///message reference from "+CMGS: <mr>"
+fn parse_reference(answer: &str) -> Option<u8> {
+    let pos = answer.find(ANSWER_SMS_SENT)?;
+    let rest = &answer[pos + ANSWER_SMS_SENT.len()..];
+    let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+fn find_bytes(data: &[u8], pattern: &[u8]) -> Option<usize> {
+    data.windows(pattern.len()).position(|x| x == pattern)
+}
+
+/// Transport of packet data connection
+#[allow(dead_code)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// State of packet data, only one connection at a time
+#[derive(Copy, Clone, PartialEq)]
+pub enum ConnectionState {
+    Closed,
+    GprsUp,
+    Connected,
+}
+
 pub struct Sim900 {
     state: Sim900State,
     pin: Pxx<Output<PushPull>>,
     timer: Timer,
     main_state: u8,
     sub_state: u8,
+    reference: Option<u8>,
+    connection: ConnectionState,
 }
 #[allow(dead_code)]
 fn expect_str<'a>(r: Result<(), RequestError<'a>>, s: &str) -> Result<(), RequestError<'a>> {
@@ -132,7 +359,9 @@ fn expect_str<'a>(r: Result<(), RequestError<'a>>, s: &str) -> Result<(), Reques
 }
 #[allow(dead_code)]
 impl Sim900 {
-    const TIMEOUT: MilliSeconds = MilliSeconds(200_u16);
+    const TIMEOUT: MilliSeconds = MilliSeconds(200);
+    const STATUS_REPORT_TIMEOUT: MilliSeconds = MilliSeconds(1000);
+    const STATUS_REPORT_READS: u8 = 3;
 
     pub fn new(mut pin: Pxx<Output<PushPull>>) -> Self {
         pin.set_low().unwrap();
@@ -142,6 +371,8 @@ impl Sim900 {
             timer: Timer::new(),
             main_state: 0,
             sub_state: 0,
+            reference: None,
+            connection: ConnectionState::Closed,
         }
     }
     fn toggle_power(&mut self) -> Option<()> {
@@ -164,6 +395,10 @@ impl Sim900 {
             _ => None,
         }
     }
+    ///modem reported low supply voltage since last call
+    pub fn take_under_voltage(&mut self) -> bool {
+        UNDER_VOLTAGE.swap(false, Ordering::Relaxed)
+    }
     pub fn get_state(&self) -> Sim900State {
         return self.state;
     }
@@ -173,13 +408,66 @@ impl Sim900 {
             request(SIM900_DATA_MODE, Sim900::TIMEOUT) //set 9600 bod in data mode
                                                        //request(SIM900_UTF_MODE, Sim900::TIMEOUT) //set Unicode for sms
         }())?;
-        let res = request(SIM900_GET_SIM_STATUS, 1.sec()); //check sim
-        if let Err(RequestError::EAnswerError) = res {
-            self.state = Sim900State::NoSim;
-            res
-        } else {
-            self.handle_request(res)
+        let _ = request(SIM900_NETWORK_TIME_ON, Sim900::TIMEOUT); //old firmware has no CLTS, ignore
+        let _ = request(SIM900_STATUS_REPORTS_ON, Sim900::TIMEOUT);
+        let res = request_answer(SIM900_GET_SIM_STATUS, 1.sec()); //check sim
+        match res {
+            Err(RequestError::EAnswerError) => {
+                self.state = Sim900State::NoSim;
+                Err(RequestError::EAnswerError)
+            }
+            Ok(answer) if answer.find(ANSWER_SIM_READY).is_none() => {
+                //SIM PIN or SIM PUK
+                self.state = Sim900State::SimLocked;
+                Err(RequestError::EAnswerUnknown(answer))
+            }
+            _ => self.handle_request(res).map(|_| ()),
+        }
+    }
+    ///registered in home network or roaming
+    pub fn is_registered<'a>(&mut self) -> Result<(), RequestError<'a>> {
+        let answer = self.handle_request(request_answer(SIM900_GET_REGISTRATION, Sim900::TIMEOUT))?;
+        let pos = answer.find(ANSWER_REGISTRATION).ok_or(RequestError::EAnswerUnknown(answer))?;
+        //+CREG: <n>,<stat>
+        match answer[pos + ANSWER_REGISTRATION.len()..].get(2..3) {
+            Some("1") | Some("5") => Ok(()),
+            _ => Err(RequestError::EAnswerUnknown(answer)),
+        }
+    }
+    ///rssi 0..31, 99 - unknown
+    pub fn signal_quality<'a>(&mut self) -> Result<u8, RequestError<'a>> {
+        let answer = self.handle_request(request_answer(SIM900_GET_SIGNAL, Sim900::TIMEOUT))?;
+        let pos = answer.find(ANSWER_SIGNAL).ok_or(RequestError::EAnswerUnknown(answer))?;
+        let rest = &answer[pos + ANSWER_SIGNAL.len()..];
+        let end = rest.find(',').ok_or(RequestError::EAnswerUnknown(answer))?;
+        rest[..end].parse().map_err(|_| RequestError::EAnswerUnknown(answer))
+    }
+    ///balance from operator ussd answer, fraction is dropped
+    pub fn get_balance<'a>(&mut self, ussd: &str) -> Result<i32, RequestError<'a>> {
+        let mut cmd: String<U32> = String::from(SIM900_USSD);
+        cmd.push_str(ussd)?;
+        cmd.push_str("\"\r\n")?;
+        //operator answers in several seconds after OK
+        let answer = self.handle_request(request_answer(&cmd, 8.sec()))?;
+        let pos = answer.find(ANSWER_USSD).ok_or(RequestError::EAnswerUnknown(answer))?;
+        //+CUSD: <m>,"<text>",<dcs>
+        let rest = &answer[pos..];
+        let start = rest.find('"').ok_or(RequestError::EAnswerUnknown(answer))? + 1;
+        let text = &rest[start..];
+        let text = &text[..text.find('"').unwrap_or(text.len())];
+        parse_balance(text).ok_or(RequestError::EAnswerUnknown(answer))
+    }
+    ///network time, valid only after registration with CLTS enabled
+    pub fn get_time<'a>(&mut self) -> Result<DateTime, RequestError<'a>> {
+        let answer = self.handle_request(request_answer(SIM900_GET_TIME, Sim900::TIMEOUT))?;
+        let pos = answer.find(ANSWER_TIME).ok_or(RequestError::EAnswerUnknown(answer))?;
+        let time = DateTime::parse(&answer[pos + ANSWER_TIME.len()..])
+            .ok_or(RequestError::EAnswerUnknown(answer))?;
+        //modem reports its default date when network time was not received
+        if time.year < 2020 {
+            return Err(RequestError::EAnswerUnknown(answer));
         }
+        Ok(time)
     }
     pub fn is_online<'a>(&mut self) -> Result<(), RequestError<'a>> {
         self.handle_request(request(SIM900_AT, Sim900::TIMEOUT))
@@ -238,6 +526,7 @@ impl Sim900 {
             1 => {
                 if self.toggle_power().is_some() {
                     self.main_state = 0;
+                    self.connection = ConnectionState::Closed;
                     Some(())
                 } else {
                     None
@@ -250,10 +539,10 @@ impl Sim900 {
         }
     }
 
-    fn handle_request<'a>(
+    fn handle_request<'a, T>(
         &mut self,
-        x: Result<(), RequestError<'a>>,
-    ) -> Result<(), RequestError<'a>> {
+        x: Result<T, RequestError<'a>>,
+    ) -> Result<T, RequestError<'a>> {
         match x {
             Ok(_) => self.state = Sim900State::Good,
             Err(RequestError::EBadRequest) => self.state = Sim900State::Good,
@@ -282,26 +571,215 @@ impl Sim900 {
         }())
     }
     pub fn send_pdu_sms(&mut self, msg: &str) -> bool {
+        self.reference = None;
         if let Sim900State::Good = self.state {
-            self.handle_request(|| -> Result<(), RequestError> {
+            let res = self.handle_request(|| -> Result<Option<u8>, RequestError> {
                 let mut cmd: String<U50> = String::from("AT+CMGS=");
                 let len = (msg.len() - 2) / 2;
                 write!(cmd, "{}\r", len)?;
                 expect_str(request(&cmd, Sim900::TIMEOUT), ">")?;
                 let _ = request(&msg, Sim900::TIMEOUT);
                 atomic::compiler_fence(Ordering::SeqCst);
-                let answer = expect_str(request(&SIM900_CMD_ENTER, 3.sec()), "+CMGS"); //ignore answer
-                if let Err(RequestError::EAnswerError) = answer {
-                    return Err(RequestError::EAnswerError);
-                } else {
-                    Ok(())
+                match request_answer(SIM900_CMD_ENTER, 3.sec()) {
+                    Err(RequestError::EAnswerError) => Err(RequestError::EAnswerError),
+                    Ok(answer) | Err(RequestError::EAnswerUnknown(answer)) => {
+                        Ok(parse_reference(answer))
+                    }
+                    _ => Ok(None), //ignore answer
                 }
-            }())
-            .is_ok()
+            }());
+            match res {
+                Ok(reference) => {
+                    self.reference = reference;
+                    true
+                }
+                Err(_) => false,
+            }
         } else {
             false
         }
     }
+    ///message reference of last sent sms, status report comes with it
+    pub fn message_reference(&self) -> Option<u8> {
+        self.reference
+    }
+    ///listen for +CDS about one second, modem must stay powered
+    pub fn poll_status_report(&mut self) -> Option<StatusReport> {
+        let text = wait_urc(1.sec())?;
+        let pos = text.find(URC_STATUS_REPORT)?;
+        //header and pdu may come in different reads, keep what is read already
+        let mut urc: String<U160> = String::new();
+        for c in text[pos..].chars() {
+            if urc.push(c).is_err() {
+                break;
+            }
+        }
+        for _ in 0..Self::STATUS_REPORT_READS {
+            if let Some(x) = status_report_pdu(&urc) {
+                return pdu::decode_status_report(x);
+            }
+            let more = wait_urc(Self::STATUS_REPORT_TIMEOUT)?;
+            urc.push_str(more).ok()?;
+        }
+        None
+    }
+    ///ring phone for duration and hang up, blocking
+    pub fn call<'a, T: TimeType>(
+        &mut self,
+        phone: &str,
+        duration: T,
+    ) -> Result<(), RequestError<'a>> {
+        let mut cmd: String<U32> = String::from(SIM900_RING);
+        cmd.push_str(phone)?;
+        cmd.push_str(SIM900_TERMINATOR)?;
+        cmd.push_str(SIM900_END)?;
+        self.handle_request(request(&cmd, 1.sec()))?;
+        Timer::new().wait(duration);
+        self.handle_request(request(SIM900_DISCONNECT, Sim900::TIMEOUT))
+    }
+}
+
+#[allow(dead_code)]
+impl Sim900 {
+    pub fn connection_state(&self) -> ConnectionState {
+        self.connection
+    }
+    ///attach to gprs with access point and get ip address, modem must be registered
+    pub fn gprs_up<'a>(&mut self, apn: &Apn) -> Result<(), RequestError<'a>> {
+        //previous context may be left after reset of controller
+        let _ = request(SIM900_GPRS_DOWN, 2.sec());
+        self.connection = ConnectionState::Closed;
+        let mut cmd: String<U100> = String::from(SIM900_SET_APN);
+        write!(cmd, "{}\",\"{}\",\"{}\"\r\n", apn.name, apn.user, apn.password)?;
+        self.handle_request(|| -> Result<(), RequestError> {
+            request(SIM900_SINGLE_CONNECTION, Sim900::TIMEOUT)?;
+            request(SIM900_DATA_HEADER_ON, Sim900::TIMEOUT)?;
+            request(&cmd, Sim900::TIMEOUT)?;
+            //activation takes seconds
+            request(SIM900_GPRS_UP, 10.sec())
+        }())?;
+        //ip address is answered without OK
+        match self.handle_request(request(SIM900_GET_IP, 1.sec())) {
+            Err(RequestError::EAnswerUnknown(answer)) if answer.contains('.') => {
+                self.state = Sim900State::Good;
+                self.connection = ConnectionState::GprsUp;
+                Ok(())
+            }
+            Err(x) => Err(x),
+            Ok(_) => Err(RequestError::EAnswerError),
+        }
+    }
+    ///open connection to server, gprs must be up
+    pub fn connect<'a>(
+        &mut self,
+        protocol: Protocol,
+        host: &str,
+        port: u16,
+    ) -> Result<(), RequestError<'a>> {
+        let mut cmd: String<U100> = String::from(SIM900_CONNECT);
+        let protocol = match protocol {
+            Protocol::Tcp => "TCP",
+            Protocol::Udp => "UDP",
+        };
+        write!(cmd, "{}\",\"{}\",\"{}\"\r\n", protocol, host, port)?;
+        //OK comes at once, connection result later
+        let answer = match self.handle_request(request_answer(&cmd, 10.sec())) {
+            Ok(x) | Err(RequestError::EAnswerUnknown(x)) => x,
+            Err(x) => return Err(x),
+        };
+        if answer.contains(ANSWER_CONNECTED) || answer.contains(ANSWER_ALREADY_CONNECTED) {
+            self.connection = ConnectionState::Connected;
+            Ok(())
+        } else {
+            self.state = Sim900State::BadAnswer;
+            Err(RequestError::EAnswerUnknown(answer))
+        }
+    }
+    ///send data over open connection, waits for prompt and confirmation
+    pub fn send_data<'a>(&mut self, data: &[u8]) -> Result<(), RequestError<'a>> {
+        if self.connection != ConnectionState::Connected {
+            return Err(RequestError::EBadRequest);
+        }
+        let mut cmd: String<U32> = String::from(SIM900_SEND_DATA);
+        write!(cmd, "{}\r", data.len())?;
+        self.handle_request(expect_str(request(&cmd, Sim900::TIMEOUT), ANSWER_ENTER_DATA))?;
+        let answer = match write_bytes_and_wait_answer(data, 3.sec()) {
+            Some(Span(x, len)) => str::from_utf8(&x[0..len]).unwrap_or(""),
+            None => return self.handle_request(Err(RequestError::ETimeout)),
+        };
+        if answer.contains(ANSWER_DATA_SENT) {
+            Ok(())
+        } else {
+            self.check_closed(answer.as_bytes());
+            self.handle_request(Err(RequestError::EAnswerUnknown(answer)))
+        }
+    }
+    ///listen about one second and copy received data to buf, what does not fit is dropped
+    pub fn receive_data(&mut self, buf: &mut [u8]) -> usize {
+        _USART.get().prepare_to_read();
+        let mut data = match _USART.get().read_timeout(1.sec()) {
+            Some(Span(x, len)) => &x[0..len],
+            None => return 0,
+        };
+        let mut count = 0;
+        //+IPD,<len>:<data>, urcs are looked for only outside of data
+        while let Some(pos) = find_bytes(data, URC_DATA) {
+            self.check_closed(&data[..pos]);
+            data = &data[pos + URC_DATA.len()..];
+            let colon = match data.iter().position(|x| *x == b':') {
+                Some(x) => x,
+                None => break,
+            };
+            let len = str::from_utf8(&data[..colon])
+                .ok()
+                .and_then(|x| x.parse::<usize>().ok())
+                .unwrap_or(0);
+            data = &data[colon + 1..];
+            let len = len.min(data.len());
+            let copied = len.min(buf.len() - count);
+            buf[count..count + copied].copy_from_slice(&data[..copied]);
+            count += copied;
+            data = &data[len..];
+        }
+        self.check_closed(data);
+        count
+    }
+    ///connection closed by server or gprs lost
+    fn check_closed(&mut self, data: &[u8]) {
+        if find_bytes(data, URC_GPRS_LOST).is_some() {
+            self.connection = ConnectionState::Closed;
+        } else if find_bytes(data, URC_CLOSED).is_some() {
+            self.connection = ConnectionState::GprsUp;
+        }
+    }
+    pub fn close<'a>(&mut self) -> Result<(), RequestError<'a>> {
+        if self.connection == ConnectionState::Connected {
+            self.connection = ConnectionState::GprsUp;
+            //answer is CLOSE OK without OK
+            return self.handle_request(expect_str(request(SIM900_CLOSE, 1.sec()), "CLOSE"));
+        }
+        Ok(())
+    }
+    pub fn gprs_down<'a>(&mut self) -> Result<(), RequestError<'a>> {
+        self.connection = ConnectionState::Closed;
+        self.handle_request(expect_str(request(SIM900_GPRS_DOWN, 2.sec()), ANSWER_GPRS_DOWN))
+    }
+}
+
+impl ByteStream for Sim900 {
+    type Error = ();
+    fn write(&mut self, data: &[u8]) -> Result<(), ()> {
+        self.send_data(data).map_err(|_| ())
+    }
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        if self.connection != ConnectionState::Connected {
+            return Err(());
+        }
+        Ok(self.receive_data(buf))
+    }
+    fn is_connected(&self) -> bool {
+        self.connection == ConnectionState::Connected
+    }
 }
 
 impl<'a> From<()> for RequestError<'_> {
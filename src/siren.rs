@@ -0,0 +1,115 @@
+use crate::hal::gpio::{Output, PushPull, Pxx};
+use crate::hardware::system_timer::SystemTimer;
+use crate::timer::{MilliSeconds, TimeType};
+use core::cell::RefCell;
+use cortex_m::interrupt::Mutex;
+use embedded_hal::digital::v2::OutputPin;
+
+/// Pulses of siren, off_ms = 0 (or both 0) means continuous sound
+#[derive(Copy, Clone)]
+pub struct Pattern {
+    pub on_ms: u16,
+    pub off_ms: u16,
+}
+
+#[allow(dead_code)]
+impl Pattern {
+    pub const CONTINUOUS: Pattern = Pattern {
+        on_ms: 1000,
+        off_ms: 0,
+    };
+    pub const PULSE: Pattern = Pattern {
+        on_ms: 500,
+        off_ms: 500,
+    };
+    const CHIRP: Pattern = Pattern {
+        on_ms: 60,
+        off_ms: 200,
+    };
+}
+
+struct SirenState {
+    pin: Pxx<Output<PushPull>>,
+    pattern: Pattern,
+    remaining_ms: u32,
+    phase_ms: u32,
+}
+
+impl SirenState {
+    fn tick(&mut self) {
+        if self.remaining_ms == 0 {
+            return;
+        }
+        self.remaining_ms -= 1;
+        let period = self.pattern.on_ms as u32 + self.pattern.off_ms as u32;
+        //empty pattern would divide by zero, sound it steady
+        let is_on = if period == 0 {
+            true
+        } else {
+            self.phase_ms = (self.phase_ms + 1) % period;
+            self.phase_ms < self.pattern.on_ms as u32
+        };
+        if self.remaining_ms > 0 && is_on {
+            self.pin.set_high().unwrap();
+        } else {
+            self.pin.set_low().unwrap();
+        }
+    }
+}
+
+static _SIREN: Mutex<RefCell<Option<SirenState>>> = Mutex::new(RefCell::new(None));
+
+/// Siren/strobe relay, switched from system timer interrupt so it sounds
+/// while main loop is blocked by modem
+pub struct Siren {}
+#[allow(dead_code)]
+impl Siren {
+    pub fn init(mut pin: Pxx<Output<PushPull>>) {
+        pin.set_low().unwrap();
+        cortex_m::interrupt::free(|cs| {
+            *_SIREN.borrow(cs).borrow_mut() = Some(SirenState {
+                pin,
+                pattern: Pattern::CONTINUOUS,
+                remaining_ms: 0,
+                phase_ms: 0,
+            })
+        });
+        SystemTimer::add_tick_hook(Siren::tick);
+    }
+    pub fn start<T: TimeType>(pattern: Pattern, duration: T) {
+        let duration = MilliSeconds(duration.value());
+        cortex_m::interrupt::free(|cs| {
+            if let Some(siren) = _SIREN.borrow(cs).borrow_mut().as_mut() {
+                siren.pattern = pattern;
+                siren.phase_ms = 0;
+                siren.remaining_ms = if duration.0 > u32::MAX as u64 {
+                    u32::MAX
+                } else {
+                    duration.0 as u32
+                };
+            }
+        });
+    }
+    ///short confirmation beeps, 1 - armed, 2 - disarmed
+    pub fn chirp(count: u8) {
+        let pattern = Pattern::CHIRP;
+        let period = pattern.on_ms as u64 + pattern.off_ms as u64;
+        Siren::start(pattern, MilliSeconds(period * count as u64));
+    }
+    pub fn stop() {
+        Siren::start(Pattern::CONTINUOUS, MilliSeconds(1));
+    }
+    pub fn is_active() -> bool {
+        cortex_m::interrupt::free(|cs| match _SIREN.borrow(cs).borrow().as_ref() {
+            Some(siren) => siren.remaining_ms > 0,
+            None => false,
+        })
+    }
+    fn tick() {
+        cortex_m::interrupt::free(|cs| {
+            if let Some(siren) = _SIREN.borrow(cs).borrow_mut().as_mut() {
+                siren.tick();
+            }
+        });
+    }
+}
@@ -0,0 +1,296 @@
+use crate::aux_sensors::AuxEvent;
+use crate::clock::{Clock, DateTime};
+use crate::config::CONFIG;
+use crate::crash_record::CrashKind;
+use crate::event_log::{Event, EventKind};
+use crate::ibutton::KeyId;
+use crate::indication::IndicationState;
+use crate::timer::Timer;
+use crate::zone::Zone;
+use core::fmt::{self, Write};
+use heapless::consts::*;
+use heapless::String;
+
+/// Rendered text, one sms of 70 cyrillic chars fits
+pub type SmsText = String<U160>;
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum Language {
+    Russian,
+    English,
+}
+
+/// Event type which has its own template
+#[derive(Copy, Clone, PartialEq)]
+pub enum Message {
+    Alarm,
+    Arm,
+    Disarm,
+    KeyArmed,
+    KeyDisarmed,
+    UnknownKey,
+    Duress,
+    CodeLockout,
+    Tamper,
+    ZoneFault,
+    ZoneFaultAlarm,
+    MainsLost,
+    MainsRestored,
+    BatteryLow,
+    BalanceLow,
+    WatchdogReset,
+    CrashReset,
+    PowerCycledArmed,
+    AutoArmFailed,
+    GsmFault,
+    TemperatureHigh,
+    TemperatureLow,
+    TemperatureNormal,
+    TemperatureLost,
+    Flood,
+    FloodCleared,
+    Smoke,
+    SmokeCleared,
+    Heartbeat,
+    Test,
+}
+
+/// Values of placeholders, missing ones are rendered as "-"
+#[derive(Copy, Clone, Default)]
+pub struct Values<'a> {
+    pub zone: Option<Zone>,
+    pub state: Option<IndicationState>,
+    pub signal: Option<u8>,
+    pub balance: Option<i32>,
+    pub fault: Option<u8>,
+    pub battery_mv: Option<u16>,
+    pub supply_mv: Option<u16>,
+    pub temperature: Option<i16>,
+    pub key: Option<KeyId>,
+    pub event: Option<Event>,
+    pub detail: Option<&'a str>,
+}
+
+fn russian(message: Message) -> &'static str {
+    match message {
+        Message::Alarm => "Тревога!\n{zone}!\n{time}",
+        Message::Arm => "Поставлено на охрану {time}",
+        Message::Disarm => "Снято с охраны {time}",
+        Message::KeyArmed => "Поставлено на охрану ключом {key}",
+        Message::KeyDisarmed => "Снято с охраны ключом {key}",
+        Message::UnknownKey => "Неизвестный ключ {key}",
+        Message::Duress => "Тревога!\nСнятие с охраны под принуждением!",
+        Message::CodeLockout => "Тревога!\nНеверный код снятия с охраны!",
+        Message::Tamper => "Тревога!\nВскрыт корпус!",
+        Message::ZoneFault => "Неисправность шлейфа двери",
+        Message::ZoneFaultAlarm => "Тревога!\nНеисправность шлейфа двери!\n{time}",
+        Message::MainsLost => "Пропало сетевое питание, работа от батареи {battery}",
+        Message::MainsRestored => "Сетевое питание восстановлено",
+        Message::BatteryLow => "Батарея разряжена: {battery}",
+        Message::BalanceLow => "Мало средств на счете: {balance}",
+        Message::WatchdogReset => "Перезапуск по сторожевому таймеру",
+        Message::CrashReset => "Перезапуск после сбоя: {detail}",
+        Message::PowerCycledArmed => "Перезапуск в режиме охраны",
+        Message::AutoArmFailed => "Автопостановка на охрану не выполнена: дверь открыта",
+        Message::GsmFault => "Неисправность GSM, код {fault}\n{state}\nGSM {signal} Бал {balance}",
+        Message::TemperatureHigh => "Высокая температура: {temperature}",
+        Message::TemperatureLow => "Низкая температура: {temperature}",
+        Message::TemperatureNormal => "Температура в норме: {temperature}",
+        Message::TemperatureLost => "Нет связи с датчиком температуры",
+        Message::Flood => "Тревога!\nПротечка воды!",
+        Message::FloodCleared => "Протечка устранена",
+        Message::Smoke => "Тревога!\nПожар!",
+        Message::SmokeCleared => "Пожарный шлейф в норме",
+        Message::Heartbeat => {
+            "{state}\nGSM {signal} Бал {balance}\nПит {supply} Раб {uptime}\n{event}"
+        }
+        Message::Test => "Тестовое сообщение",
+    }
+}
+
+fn english(message: Message) -> &'static str {
+    match message {
+        Message::Alarm => "Alarm!\n{zone}!\n{time}",
+        Message::Arm => "Armed {time}",
+        Message::Disarm => "Disarmed {time}",
+        Message::KeyArmed => "Armed by key {key}",
+        Message::KeyDisarmed => "Disarmed by key {key}",
+        Message::UnknownKey => "Unknown key {key}",
+        Message::Duress => "Alarm!\nDisarmed under duress!",
+        Message::CodeLockout => "Alarm!\nWrong disarm code!",
+        Message::Tamper => "Alarm!\nEnclosure opened!",
+        Message::ZoneFault => "Door loop fault",
+        Message::ZoneFaultAlarm => "Alarm!\nDoor loop fault!\n{time}",
+        Message::MainsLost => "Mains power lost, on battery {battery}",
+        Message::MainsRestored => "Mains power restored",
+        Message::BatteryLow => "Battery low: {battery}",
+        Message::BalanceLow => "Low balance: {balance}",
+        Message::WatchdogReset => "Restarted by watchdog",
+        Message::CrashReset => "Restarted after crash: {detail}",
+        Message::PowerCycledArmed => "Restarted while armed",
+        Message::AutoArmFailed => "Scheduled arming failed: door is open",
+        Message::GsmFault => "GSM fault, code {fault}\n{state}\nGSM {signal} Bal {balance}",
+        Message::TemperatureHigh => "High temperature: {temperature}",
+        Message::TemperatureLow => "Low temperature: {temperature}",
+        Message::TemperatureNormal => "Temperature is normal: {temperature}",
+        Message::TemperatureLost => "Temperature sensor lost",
+        Message::Flood => "Alarm!\nWater leak!",
+        Message::FloodCleared => "Water leak cleared",
+        Message::Smoke => "Alarm!\nFire!",
+        Message::SmokeCleared => "Fire loop is normal",
+        Message::Heartbeat => {
+            "{state}\nGSM {signal} Bal {balance}\nPwr {supply} Up {uptime}\n{event}"
+        }
+        Message::Test => "Test message",
+    }
+}
+
+fn zone_name(zone: Zone, language: Language) -> &'static str {
+    match (language, zone) {
+        (Language::Russian, Zone::Door) => "Дверь открыта",
+        (Language::Russian, Zone::Motion) => "Движение в помещении",
+        (Language::English, Zone::Door) => "Door opened",
+        (Language::English, Zone::Motion) => "Motion detected",
+    }
+}
+
+fn state_name(state: IndicationState, language: Language) -> &'static str {
+    let (russian, english) = match state {
+        IndicationState::Nothing | IndicationState::Idle | IndicationState::IdleDoorClosed => {
+            ("Снято", "Disarmed")
+        }
+        IndicationState::CheckBeforeArm
+        | IndicationState::ReadyToArm
+        | IndicationState::ExitDelay => ("Постановка", "Arming"),
+        IndicationState::Armed => ("Охрана", "Armed"),
+        IndicationState::EntryDelay => ("Вход", "Entry"),
+        IndicationState::Error => ("Ошибка", "Error"),
+        IndicationState::Tamper => ("Вскрытие", "Tamper"),
+    };
+    match language {
+        Language::Russian => russian,
+        Language::English => english,
+    }
+}
+
+///short name for status sms
+fn event_name(kind: EventKind, language: Language) -> &'static str {
+    let (russian, english) = match kind {
+        EventKind::Reset(_) | EventKind::PowerCycledArmed => ("Перезапуск", "Restart"),
+        EventKind::Crash(_) => ("Сбой", "Crash"),
+        EventKind::Alarm(_) => ("Тревога", "Alarm"),
+        EventKind::MainsLost => ("Нет сети", "Mains lost"),
+        EventKind::MainsRestored => ("Есть сеть", "Mains ok"),
+        EventKind::BatteryLow => ("Батарея", "Battery low"),
+        EventKind::Tamper => ("Вскрытие", "Tamper"),
+        EventKind::ZoneFault => ("Шлейф", "Loop fault"),
+        EventKind::CodeLockout => ("Код", "Code lockout"),
+        EventKind::Duress => ("Принуждение", "Duress"),
+        EventKind::Key(_) => ("Ключ", "Key"),
+        EventKind::UnknownKey(_) => ("Чужой ключ", "Unknown key"),
+        EventKind::Aux(AuxEvent::Flood) => ("Протечка", "Leak"),
+        EventKind::Aux(AuxEvent::FloodCleared) => ("Нет протечки", "Leak cleared"),
+        EventKind::Aux(AuxEvent::Smoke) => ("Пожар", "Fire"),
+        EventKind::Aux(AuxEvent::SmokeCleared) => ("Нет пожара", "Fire cleared"),
+        EventKind::Aux(_) => ("Температура", "Temperature"),
+        EventKind::AutoArm | EventKind::AutoArmFailed => ("Автоохрана", "Auto arm"),
+        EventKind::AutoDisarm => ("Автоснятие", "Auto disarm"),
+        EventKind::NotDelivered => ("Не доставлено", "Not delivered"),
+    };
+    match language {
+        Language::Russian => russian,
+        Language::English => english,
+    }
+}
+
+///"19.10 08:00"
+fn write_time(out: &mut SmsText, time: &DateTime) -> fmt::Result {
+    write!(
+        out,
+        "{:02}.{:02} {:02}:{:02}",
+        time.day, time.month, time.hour, time.minute
+    )
+}
+
+///"12.3V" from millivolts
+fn write_voltage(out: &mut SmsText, mv: u16, language: Language) -> fmt::Result {
+    let unit = match language {
+        Language::Russian => "В",
+        Language::English => "V",
+    };
+    write!(out, "{}.{}{}", mv / 1000, mv % 1000 / 100, unit)
+}
+
+///"-1.5°C" from tenths of degree
+fn write_temperature(out: &mut SmsText, t: i16) -> fmt::Result {
+    let sign = if t < 0 { "-" } else { "" };
+    let abs = (t as i32).abs();
+    write!(out, "{}{}.{}°C", sign, abs / 10, abs % 10)
+}
+
+fn write_value(out: &mut SmsText, name: &str, values: &Values, language: Language) -> fmt::Result {
+    let res = match name {
+        "zone" => values.zone.map(|x| out.write_str(zone_name(x, language))),
+        "state" => values.state.map(|x| out.write_str(state_name(x, language))),
+        //time of day is unknown before network time
+        "time" => match Clock::is_synced() {
+            true => Some(write_time(out, &Clock::now())),
+            false => None,
+        },
+        "signal" => values.signal.map(|x| write!(out, "{}", x)),
+        "balance" => values.balance.map(|x| write!(out, "{}", x)),
+        "fault" => values.fault.map(|x| write!(out, "{}", x)),
+        "battery" => values.battery_mv.map(|x| write_voltage(out, x, language)),
+        "supply" => values.supply_mv.map(|x| write_voltage(out, x, language)),
+        "temperature" => values.temperature.map(|x| write_temperature(out, x)),
+        "key" => values.key.map(|x| write!(out, "{}", x)),
+        "event" => values.event.map(|x| {
+            write!(out, "{} ", event_name(x.kind, language))?;
+            //where it crashed, full message is only in crash sms
+            match x.kind {
+                EventKind::Crash(info) if info.kind == CrashKind::HardFault => {
+                    write!(out, "PC={:08X} ", info.pc)?
+                }
+                EventKind::Crash(info) => write!(out, "L{} ", info.line)?,
+                _ => {}
+            }
+            write_time(out, &x.time)
+        }),
+        "detail" => values.detail.map(|x| out.write_str(x)),
+        "uptime" => {
+            let hours = Timer::now().0 / 3_600_000;
+            Some(match language {
+                Language::Russian => write!(out, "{}д{}ч", hours / 24, hours % 24),
+                Language::English => write!(out, "{}d{}h", hours / 24, hours % 24),
+            })
+        }
+        //unknown placeholder is left as is
+        _ => Some(write!(out, "{{{}}}", name)),
+    };
+    res.unwrap_or_else(|| out.write_char('-'))
+}
+
+/// template of configured language with placeholders replaced, text which does not fit is cut
+pub fn render(message: Message, values: &Values) -> SmsText {
+    let language = CONFIG.language;
+    let mut rest = match language {
+        Language::Russian => russian(message),
+        Language::English => english(message),
+    };
+    let mut res = SmsText::new();
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(x) => start + x,
+            None => break,
+        };
+        if res.write_str(&rest[..start]).is_err()
+            || write_value(&mut res, &rest[start + 1..end], values, language).is_err()
+        {
+            return res;
+        }
+        rest = &rest[end + 1..];
+    }
+    let _ = res.write_str(rest);
+    res
+}
@@ -0,0 +1,67 @@
+use crate::hardware::one_wire::{crc8, OneWire, CMD_SKIP_ROM};
+use crate::timer::{MilliSeconds, Seconds, Timer};
+
+const CMD_CONVERT: u8 = 0x44;
+const CMD_READ_SCRATCHPAD: u8 = 0xBE;
+
+/// DS18B20, the only device on its 1-Wire bus
+pub struct Thermometer {
+    bus: OneWire,
+    timer: Timer,
+    is_converting: bool,
+}
+
+#[allow(dead_code)]
+impl Thermometer {
+    const PERIOD: Seconds = Seconds(30);
+    const CONVERSION: MilliSeconds = MilliSeconds(750);
+    pub fn new(bus: OneWire) -> Self {
+        Thermometer {
+            bus,
+            timer: Timer::new(),
+            is_converting: false,
+        }
+    }
+    fn start(&mut self) -> bool {
+        if !self.bus.reset() {
+            return false;
+        }
+        self.bus.write_byte(CMD_SKIP_ROM);
+        self.bus.write_byte(CMD_CONVERT);
+        true
+    }
+    fn read(&mut self) -> Result<i16, ()> {
+        if !self.bus.reset() {
+            return Err(());
+        }
+        self.bus.write_byte(CMD_SKIP_ROM);
+        self.bus.write_byte(CMD_READ_SCRATCHPAD);
+        let mut data = [0u8; 9];
+        self.bus.read_bytes(&mut data);
+        if crc8(&data) != 0 || data.iter().all(|x| *x == 0) {
+            return Err(());
+        }
+        //1/16 degree
+        let raw = i16::from_le_bytes([data[0], data[1]]);
+        Ok((raw as i32 * 10 / 16) as i16)
+    }
+    ///temperature in tenths of degree once per period, Err if sensor does not answer
+    pub fn poll(&mut self) -> Option<Result<i16, ()>> {
+        if self.is_converting {
+            if self.timer.waiting(&Self::CONVERSION) {
+                return None;
+            }
+            self.is_converting = false;
+            return Some(self.read());
+        }
+        if self.timer.waiting(&Self::PERIOD) {
+            return None;
+        }
+        self.timer.reset();
+        if !self.start() {
+            return Some(Err(()));
+        }
+        self.is_converting = true;
+        None
+    }
+}
@@ -1,8 +1,9 @@
 use crate::hal::pac::TIM2;
 use crate::hardware::system_timer::{CounterType, SystemTimer};
+use crate::hardware::watchdog::Watchdog;
 use core::sync::atomic::{self, Ordering};
 pub struct Timer {
-    time: CounterType,
+    time: Instant,
 }
 #[allow(dead_code)]
 impl Timer {
@@ -10,42 +11,64 @@ impl Timer {
         SystemTimer::init(tim, clocks, apb1);
     }
 
+    pub fn now() -> Instant {
+        Instant(SystemTimer::now())
+    }
+
     pub fn new() -> Timer {
-        Timer {
-            time: SystemTimer::now(),
-        }
+        Timer { time: Timer::now() }
     }
-    pub fn elapsed(&self) -> CounterType {
-        return SystemTimer::now() - self.time;
+    pub fn elapsed(&self) -> MilliSeconds {
+        Timer::now().saturating_duration_since(self.time)
     }
 
     pub fn reset(&mut self) {
-        self.time = SystemTimer::now();
+        self.time = Timer::now();
     }
     pub fn wait<T: TimeType>(&mut self, time: T) {
         self.reset();
         while self.waiting(&time) {
             atomic::compiler_fence(Ordering::SeqCst);
-            continue;
+            //woken up by system timer every millisecond
+            cortex_m::asm::wfi();
+            Watchdog::feed_in_phase();
         }
     }
 
     pub fn waiting<T: TimeType>(&mut self, time: &T) -> bool {
-        let diff: CounterType = CounterType::wrapping_sub(SystemTimer::now(), self.time);
-        return if diff < time.value() { true } else { false };
+        self.elapsed().0 < time.value()
     }
 
     pub fn every<T: TimeType>(&mut self, time: T) -> bool {
-        let now = SystemTimer::now();
-
-        let diff: CounterType = CounterType::wrapping_sub(now, self.time);
-        if diff >= time.value() {
+        let now = Timer::now();
+        if now.saturating_duration_since(self.time).0 >= time.value() {
             self.time = now;
             return true;
         }
         false
     }
 }
+
+/// Point of monotonic system time, milliseconds since start
+#[derive(PartialEq, PartialOrd, Clone, Copy)]
+pub struct Instant(pub CounterType);
+
+#[allow(dead_code)]
+impl Instant {
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<MilliSeconds> {
+        self.0.checked_sub(earlier.0).map(MilliSeconds)
+    }
+    pub fn saturating_duration_since(&self, earlier: Instant) -> MilliSeconds {
+        MilliSeconds(self.0.saturating_sub(earlier.0))
+    }
+    pub fn checked_add<T: TimeType>(&self, time: T) -> Option<Instant> {
+        self.0.checked_add(time.value()).map(Instant)
+    }
+    pub fn saturating_add<T: TimeType>(&self, time: T) -> Instant {
+        Instant(self.0.saturating_add(time.value()))
+    }
+}
+
 /// Time unit
 #[derive(PartialEq, PartialOrd, Clone, Copy)]
 pub struct MilliSeconds(pub CounterType);
@@ -53,9 +76,18 @@ pub struct MilliSeconds(pub CounterType);
 #[derive(PartialEq, PartialOrd, Clone, Copy)]
 pub struct Seconds(pub CounterType);
 
+#[derive(PartialEq, PartialOrd, Clone, Copy)]
+pub struct Minutes(pub CounterType);
+
+#[derive(PartialEq, PartialOrd, Clone, Copy)]
+pub struct Hours(pub CounterType);
+
+#[allow(dead_code)]
 pub trait CounterTypeExt {
     fn mil(self) -> MilliSeconds;
     fn sec(self) -> Seconds;
+    fn minutes(self) -> Minutes;
+    fn hours(self) -> Hours;
 }
 
 impl CounterTypeExt for CounterType {
@@ -65,8 +97,15 @@ impl CounterTypeExt for CounterType {
     fn sec(self) -> Seconds {
         Seconds(self)
     }
+    fn minutes(self) -> Minutes {
+        Minutes(self)
+    }
+    fn hours(self) -> Hours {
+        Hours(self)
+    }
 }
 
+/// Duration in milliseconds, conversions saturate instead of overflow
 pub trait TimeType {
     fn value(&self) -> CounterType;
 }
@@ -79,14 +118,37 @@ impl TimeType for MilliSeconds {
 
 impl TimeType for Seconds {
     fn value(&self) -> CounterType {
-        let ms = MilliSeconds::from(*self);
-        ms.0
+        self.0.saturating_mul(1_000)
+    }
+}
+
+impl TimeType for Minutes {
+    fn value(&self) -> CounterType {
+        self.0.saturating_mul(60_000)
+    }
+}
+
+impl TimeType for Hours {
+    fn value(&self) -> CounterType {
+        self.0.saturating_mul(3_600_000)
     }
 }
 
 impl From<Seconds> for MilliSeconds {
     fn from(val: Seconds) -> Self {
-        Self(val.0 * 1_000)
+        Self(val.value())
+    }
+}
+
+impl From<Minutes> for MilliSeconds {
+    fn from(val: Minutes) -> Self {
+        Self(val.value())
+    }
+}
+
+impl From<Hours> for MilliSeconds {
+    fn from(val: Hours) -> Self {
+        Self(val.value())
     }
 }
 
@@ -96,12 +158,12 @@ impl From<MilliSeconds> for Seconds {
     }
 }
 
-use core::ops::Add;
+use core::ops::{Add, Sub};
 impl Add for Seconds {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
-        Self(self.0 + other.0)
+        Self(self.0.saturating_add(other.0))
     }
 }
 
@@ -109,6 +171,22 @@ impl Add for MilliSeconds {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
-        Self(self.0 + other.0)
+        Self(self.0.saturating_add(other.0))
+    }
+}
+
+impl Sub for Seconds {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+}
+
+impl Sub for MilliSeconds {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
     }
 }
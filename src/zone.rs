@@ -0,0 +1,36 @@
+use crate::config::CONFIG;
+use crate::timer::Timer;
+
+/// Guarded zone, source of alarm
+#[derive(Copy, Clone, PartialEq)]
+pub enum Zone {
+    Door,
+    Motion,
+}
+
+/// Trips of zones while armed. With cross-zoning alarm needs trips of two
+/// different zones within window, one zone alone is ignored
+pub struct ArmedZones {
+    first: Option<(Zone, Timer)>,
+}
+
+#[allow(dead_code)]
+impl ArmedZones {
+    pub fn new() -> Self {
+        ArmedZones { first: None }
+    }
+    ///true if trip of zone is confirmed
+    pub fn trip(&mut self, zone: Zone) -> bool {
+        if !CONFIG.cross_zoning {
+            return true;
+        }
+        if let Some((first, timer)) = self.first.as_mut() {
+            if *first != zone && timer.waiting(&CONFIG.cross_zone_window) {
+                self.first = None;
+                return true;
+            }
+        }
+        self.first = Some((zone, Timer::new()));
+        false
+    }
+}